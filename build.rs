@@ -0,0 +1,15 @@
+use std::env;
+
+fn main() {
+    // The x11 crate only provides keysyms and types, so the xlib backend links
+    // to the libraries itself, unless x11rb replaces it.
+    let linux = env::var("CARGO_CFG_TARGET_OS").as_deref() == Ok("linux");
+    if linux && env::var_os("CARGO_FEATURE_X11RB").is_none() {
+        for (library, version) in [("x11", "1.4.99.1"), ("xtst", "1.2"), ("xi", "1.7")] {
+            pkg_config::Config::new()
+                .atleast_version(version)
+                .probe(library)
+                .unwrap();
+        }
+    }
+}
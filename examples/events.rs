@@ -0,0 +1,11 @@
+use rdev::{events, EventType, Key};
+
+fn main() {
+    // No callback needed, events arrive as an iterator.
+    for event in events().expect("Could not listen") {
+        println!("Received {:?}", event);
+        if event.event_type == EventType::KeyPress(Key::Escape) {
+            break;
+        }
+    }
+}
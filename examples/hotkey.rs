@@ -0,0 +1,17 @@
+use rdev::{Hotkey, HotkeyManager, Key, Trigger};
+
+fn main() {
+    let mut manager = HotkeyManager::new();
+    manager.register(
+        Hotkey::new([Key::ControlLeft, Key::ShiftLeft], Key::KeyK),
+        Trigger::Press,
+        || println!("Ctrl+Shift+K pressed"),
+    );
+    manager.register("ctrl+shift+k".parse().unwrap(), Trigger::Release, || {
+        println!("Ctrl+Shift+K released")
+    });
+    // This will block.
+    if let Err(error) = manager.listen() {
+        println!("Error: {:?}", error)
+    }
+}
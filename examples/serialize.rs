@@ -1,4 +1,4 @@
-use rdev::{Event, EventType, Key};
+use rdev::{Event, EventType, Key, Modifiers};
 use std::time::SystemTime;
 
 fn main() {
@@ -6,6 +6,8 @@ fn main() {
         event_type: EventType::KeyPress(Key::KeyS),
         time: SystemTime::now(),
         name: Some(String::from("S")),
+        modifiers: Modifiers::SHIFT,
+        device: None,
     };
 
     let serialized = serde_json::to_string(&event).unwrap();
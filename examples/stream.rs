@@ -0,0 +1,14 @@
+use futures::StreamExt;
+use rdev::{listen_stream_bounded, OverflowPolicy};
+use std::num::NonZeroUsize;
+
+#[tokio::main]
+async fn main() {
+    // Keep the latest 1024 events if we can't keep up.
+    let capacity = NonZeroUsize::new(1024).unwrap();
+    let mut events =
+        listen_stream_bounded(capacity, OverflowPolicy::DropOldest).expect("Could not listen");
+    while let Some(event) = events.next().await {
+        println!("Received {:?}", event);
+    }
+}
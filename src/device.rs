@@ -0,0 +1,158 @@
+use crate::rdev::DeviceInfo;
+#[cfg(all(feature = "unstable_grab", target_os = "linux"))]
+use crate::rdev::{Event, EventType, GrabError};
+#[cfg(all(feature = "unstable_grab", target_os = "linux"))]
+use crate::GrabHandle;
+
+/// Matches input devices, see `GrabConfig`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum DeviceFilter {
+    /// Devices whose name contains this, like "Yubico".
+    Name(String),
+    /// Devices plugged in there, like usb-0000:00:14.0-2/input0.
+    Phys(String),
+    /// Devices with this vendor:product ID, as listed by lsusb.
+    Id {
+        vendor: u16,
+        product: u16,
+    },
+    Keyboard,
+    Pointer,
+}
+
+impl DeviceFilter {
+    pub fn matches(&self, device: &DeviceInfo) -> bool {
+        match self {
+            DeviceFilter::Name(name) => device.name.contains(name.as_str()),
+            DeviceFilter::Phys(phys) => device.phys.as_ref() == Some(phys),
+            DeviceFilter::Id { vendor, product } => {
+                device.vendor == *vendor && device.product == *product
+            }
+            DeviceFilter::Keyboard => device.keyboard,
+            DeviceFilter::Pointer => device.pointer,
+        }
+    }
+}
+
+/// Which devices `grab` takes over. A device gets grabbed when it matches
+/// one of the included filters, or when there are none, and none of the
+/// excluded ones. The default grabs every device. Grabbing fails with
+/// `GrabError::NoDeviceError` when none of the devices plugged in is selected.
+///
+/// ```no_run
+/// use rdev::{DeviceFilter, GrabConfig};
+///
+/// let config = GrabConfig::new()
+///     .include(DeviceFilter::Keyboard)
+///     .exclude(DeviceFilter::Name("Yubico".to_string()))
+///     .exclude(DeviceFilter::Name("Power Button".to_string()));
+/// # #[cfg(all(feature = "unstable_grab", target_os = "linux"))]
+/// // This will block.
+/// config.grab(Some).expect("Could not grab");
+/// ```
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct GrabConfig {
+    include: Vec<DeviceFilter>,
+    exclude: Vec<DeviceFilter>,
+}
+
+impl GrabConfig {
+    pub fn new() -> GrabConfig {
+        GrabConfig::default()
+    }
+
+    pub fn include(mut self, filter: DeviceFilter) -> GrabConfig {
+        self.include.push(filter);
+        self
+    }
+
+    pub fn exclude(mut self, filter: DeviceFilter) -> GrabConfig {
+        self.exclude.push(filter);
+        self
+    }
+
+    /// Whether `device` gets grabbed.
+    pub fn matches(&self, device: &DeviceInfo) -> bool {
+        (self.include.is_empty() || self.include.iter().any(|filter| filter.matches(device)))
+            && !self.exclude.iter().any(|filter| filter.matches(device))
+    }
+
+    /// Same as `grab`, for the selected devices only.
+    #[cfg(all(feature = "unstable_grab", target_os = "linux"))]
+    pub fn grab<T>(&self, callback: T) -> Result<(), GrabError>
+    where
+        T: FnMut(Event) -> Option<Event>,
+    {
+        crate::linux::grab_config(self, callback)
+    }
+
+    /// Same as `grab_emit`, for the selected devices only.
+    #[cfg(all(feature = "unstable_grab", target_os = "linux"))]
+    pub fn grab_emit<T>(&self, callback: T) -> Result<(), GrabError>
+    where
+        T: FnMut(Event) -> Vec<EventType>,
+    {
+        crate::linux::grab_emit_config(self, callback)
+    }
+
+    /// Same as `grab_with_handle`, for the selected devices only.
+    #[cfg(all(feature = "unstable_grab", target_os = "linux"))]
+    pub fn grab_with_handle<T>(&self, callback: T) -> Result<GrabHandle, GrabError>
+    where
+        T: FnMut(Event) -> Option<Event> + Send + 'static,
+    {
+        crate::linux::grab_with_handle_config(self.clone(), callback)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn device(name: &str, keyboard: bool, pointer: bool) -> DeviceInfo {
+        DeviceInfo {
+            id: 3,
+            path: None,
+            name: name.to_string(),
+            phys: Some("usb-0000:00:14.0-2/input0".to_string()),
+            vendor: 0x1050,
+            product: 0x0407,
+            keyboard,
+            pointer,
+        }
+    }
+
+    #[test]
+    fn test_filters() {
+        let yubikey = device("Yubico YubiKey OTP+FIDO+CCID", true, false);
+        assert!(DeviceFilter::Name("Yubico".to_string()).matches(&yubikey));
+        assert!(!DeviceFilter::Name("yubico".to_string()).matches(&yubikey));
+        assert!(DeviceFilter::Phys("usb-0000:00:14.0-2/input0".to_string()).matches(&yubikey));
+        assert!(!DeviceFilter::Phys("usb-0000:00:14.0-2".to_string()).matches(&yubikey));
+        assert!(DeviceFilter::Id {
+            vendor: 0x1050,
+            product: 0x0407
+        }
+        .matches(&yubikey));
+        assert!(DeviceFilter::Keyboard.matches(&yubikey));
+        assert!(!DeviceFilter::Pointer.matches(&yubikey));
+    }
+
+    #[test]
+    fn test_config() {
+        let keyboard = device("AT Translated Set 2 keyboard", true, false);
+        let mouse = device("Logitech USB Optical Mouse", false, true);
+        let yubikey = device("Yubico YubiKey OTP+FIDO+CCID", true, false);
+
+        let everything = GrabConfig::new();
+        assert!(everything.matches(&keyboard));
+        assert!(everything.matches(&mouse));
+
+        let config = GrabConfig::new()
+            .include(DeviceFilter::Keyboard)
+            .exclude(DeviceFilter::Name("Yubico".to_string()));
+        assert!(config.matches(&keyboard));
+        assert!(!config.matches(&mouse));
+        assert!(!config.matches(&yubikey));
+    }
+}
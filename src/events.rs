@@ -0,0 +1,74 @@
+use crate::rdev::{Event, ListenError};
+use crate::{listen_with_handle, ListenHandle};
+use std::sync::mpsc::{channel, Receiver, RecvTimeoutError, TryRecvError};
+use std::time::Duration;
+
+/// Blocking iterator over global events returned by `events`. The listener
+/// runs on a background thread and gets stopped when the iterator is dropped.
+/// The iterator ends if the listener fails, `stop` then tells why.
+pub struct Events {
+    receiver: Receiver<Event>,
+    handle: Option<ListenHandle>,
+}
+
+impl Events {
+    /// Waits at most `timeout` for the next event.
+    pub fn recv_timeout(&self, timeout: Duration) -> Result<Event, RecvTimeoutError> {
+        self.receiver.recv_timeout(timeout)
+    }
+
+    /// Returns the next event if one is already waiting.
+    pub fn try_recv(&self) -> Result<Event, TryRecvError> {
+        self.receiver.try_recv()
+    }
+
+    /// Stops the listener, returning the error it failed with if the
+    /// iterator ended on its own.
+    pub fn stop(mut self) -> Result<(), ListenError> {
+        match self.handle.take() {
+            Some(handle) => handle.stop(),
+            None => Ok(()),
+        }
+    }
+}
+
+impl Iterator for Events {
+    type Item = Event;
+
+    fn next(&mut self) -> Option<Event> {
+        self.receiver.recv().ok()
+    }
+}
+
+impl Drop for Events {
+    fn drop(&mut self) {
+        if let Some(handle) = self.handle.take() {
+            handle.stop().ok();
+        }
+    }
+}
+
+/// Listening to global events through a blocking iterator, without the need
+/// for a `'static` callback. The iterator ends if the listener fails.
+///
+/// ```no_run
+/// use rdev::events;
+///
+/// let mut events = events().expect("Could not listen");
+/// for event in &mut events {
+///     println!("Received {:?}", event);
+/// }
+/// if let Err(error) = events.stop() {
+///     println!("Error: {:?}", error);
+/// }
+/// ```
+pub fn events() -> Result<Events, ListenError> {
+    let (sender, receiver) = channel();
+    let handle = listen_with_handle(move |event| {
+        sender.send(event).ok();
+    })?;
+    Ok(Events {
+        receiver,
+        handle: Some(handle),
+    })
+}
@@ -0,0 +1,379 @@
+#[cfg(feature = "unstable_grab")]
+use crate::rdev::GrabError;
+use crate::rdev::{EventType, Key, ListenError, ParseHotkeyError};
+use std::collections::HashSet;
+use std::str::FromStr;
+
+/// Left and right variants of a modifier are the same key for hotkeys. AltGr
+/// stays apart from Alt, as it types characters like @ on German layouts.
+pub(crate) fn normalize(key: Key) -> Key {
+    match key {
+        Key::ShiftRight => Key::ShiftLeft,
+        Key::ControlRight => Key::ControlLeft,
+        Key::MetaRight => Key::MetaLeft,
+        key => key,
+    }
+}
+
+/// A key pressed while some other keys, usually modifiers, are held down.
+/// Left and right modifiers are equivalent, except for Alt and AltGr.
+///
+/// ```
+/// use rdev::{Hotkey, Key};
+///
+/// let hotkey = Hotkey::new([Key::ControlLeft, Key::ShiftLeft], Key::KeyK);
+/// assert_eq!("ctrl+shift+k".parse(), Ok(hotkey));
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Hotkey {
+    held: HashSet<Key>,
+    key: Key,
+}
+
+impl Hotkey {
+    pub fn new<I>(held: I, key: Key) -> Hotkey
+    where
+        I: IntoIterator<Item = Key>,
+    {
+        Hotkey {
+            held: held.into_iter().map(normalize).collect(),
+            key: normalize(key),
+        }
+    }
+
+    /// Whether pressing `key` with exactly `pressed` held down (`key` aside)
+    /// triggers the hotkey.
+    fn matches(&self, key: Key, pressed: &HashSet<Key>) -> bool {
+        self.key == key
+            && pressed.len() == self.held.len() + 1
+            && self.held.iter().all(|held| pressed.contains(held))
+    }
+}
+
+/// Parses keys separated by `+`, the last one being the key to press, like
+/// "ctrl+shift+k", "alt+f4" or "meta+space". Names are case insensitive.
+impl FromStr for Hotkey {
+    type Err = ParseHotkeyError;
+
+    fn from_str(s: &str) -> Result<Hotkey, ParseHotkeyError> {
+        let mut keys = s
+            .split('+')
+            .map(key_from_name)
+            .collect::<Result<Vec<Key>, ParseHotkeyError>>()?;
+        let key = keys.pop().ok_or(ParseHotkeyError::MissingKey)?;
+        Ok(Hotkey::new(keys, key))
+    }
+}
+
+pub(crate) fn key_from_name(name: &str) -> Result<Key, ParseHotkeyError> {
+    let name = name.trim().to_lowercase();
+    let key = match name.as_str() {
+        "" => return Err(ParseHotkeyError::MissingKey),
+        "ctrl" | "control" => Key::ControlLeft,
+        "shift" => Key::ShiftLeft,
+        "alt" | "option" => Key::Alt,
+        "altgr" => Key::AltGr,
+        "meta" | "super" | "win" | "cmd" | "command" => Key::MetaLeft,
+        "a" => Key::KeyA,
+        "b" => Key::KeyB,
+        "c" => Key::KeyC,
+        "d" => Key::KeyD,
+        "e" => Key::KeyE,
+        "f" => Key::KeyF,
+        "g" => Key::KeyG,
+        "h" => Key::KeyH,
+        "i" => Key::KeyI,
+        "j" => Key::KeyJ,
+        "k" => Key::KeyK,
+        "l" => Key::KeyL,
+        "m" => Key::KeyM,
+        "n" => Key::KeyN,
+        "o" => Key::KeyO,
+        "p" => Key::KeyP,
+        "q" => Key::KeyQ,
+        "r" => Key::KeyR,
+        "s" => Key::KeyS,
+        "t" => Key::KeyT,
+        "u" => Key::KeyU,
+        "v" => Key::KeyV,
+        "w" => Key::KeyW,
+        "x" => Key::KeyX,
+        "y" => Key::KeyY,
+        "z" => Key::KeyZ,
+        "0" => Key::Num0,
+        "1" => Key::Num1,
+        "2" => Key::Num2,
+        "3" => Key::Num3,
+        "4" => Key::Num4,
+        "5" => Key::Num5,
+        "6" => Key::Num6,
+        "7" => Key::Num7,
+        "8" => Key::Num8,
+        "9" => Key::Num9,
+        "f1" => Key::F1,
+        "f2" => Key::F2,
+        "f3" => Key::F3,
+        "f4" => Key::F4,
+        "f5" => Key::F5,
+        "f6" => Key::F6,
+        "f7" => Key::F7,
+        "f8" => Key::F8,
+        "f9" => Key::F9,
+        "f10" => Key::F10,
+        "f11" => Key::F11,
+        "f12" => Key::F12,
+        "space" => Key::Space,
+        "enter" | "return" => Key::Return,
+        "tab" => Key::Tab,
+        "esc" | "escape" => Key::Escape,
+        "backspace" => Key::Backspace,
+        "delete" | "del" => Key::Delete,
+        "insert" | "ins" => Key::Insert,
+        "home" => Key::Home,
+        "end" => Key::End,
+        "pageup" => Key::PageUp,
+        "pagedown" => Key::PageDown,
+        "up" => Key::UpArrow,
+        "down" => Key::DownArrow,
+        "left" => Key::LeftArrow,
+        "right" => Key::RightArrow,
+        "capslock" => Key::CapsLock,
+        "printscreen" => Key::PrintScreen,
+        "-" | "minus" => Key::Minus,
+        "=" | "equal" => Key::Equal,
+        "," | "comma" => Key::Comma,
+        "." | "dot" | "period" => Key::Dot,
+        "/" | "slash" => Key::Slash,
+        "\\" | "backslash" => Key::BackSlash,
+        ";" | "semicolon" => Key::SemiColon,
+        "'" | "quote" => Key::Quote,
+        "`" | "backquote" => Key::BackQuote,
+        "[" | "leftbracket" => Key::LeftBracket,
+        "]" | "rightbracket" => Key::RightBracket,
+        _ => return Err(ParseHotkeyError::UnknownKey(name)),
+    };
+    Ok(key)
+}
+
+/// When a registered hotkey calls its callback.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub enum Trigger {
+    /// Once, when the key gets pressed.
+    Press,
+    /// Once, when the key gets released after triggering.
+    Release,
+    /// When the key gets pressed, then again on every key repeat while held.
+    Hold,
+}
+
+/// Identifies a hotkey registered on a `HotkeyManager`.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub struct HotkeyId(u64);
+
+struct Registration {
+    id: HotkeyId,
+    hotkey: Hotkey,
+    trigger: Trigger,
+    callback: Box<dyn FnMut()>,
+}
+
+/// Calls closures when their hotkey gets pressed. Feed it events with
+/// `handle`, or let it drive `listen` (or `grab`) itself.
+///
+/// ```no_run
+/// use rdev::{Hotkey, HotkeyManager, Trigger};
+///
+/// let mut manager = HotkeyManager::new();
+/// let hotkey: Hotkey = "ctrl+shift+k".parse().unwrap();
+/// manager.register(hotkey, Trigger::Press, || println!("Ctrl+Shift+K"));
+/// // This will block.
+/// manager.listen().expect("Could not listen");
+/// ```
+#[derive(Default)]
+pub struct HotkeyManager {
+    next_id: u64,
+    registrations: Vec<Registration>,
+    pressed: HashSet<Key>,
+    /// Hotkeys with a release trigger waiting for their key to be released.
+    armed: Vec<HotkeyId>,
+    /// Keys whose press triggered a hotkey, so that their release matches too.
+    triggered: HashSet<Key>,
+}
+
+impl HotkeyManager {
+    pub fn new() -> HotkeyManager {
+        HotkeyManager::default()
+    }
+
+    pub fn register<F>(&mut self, hotkey: Hotkey, trigger: Trigger, callback: F) -> HotkeyId
+    where
+        F: FnMut() + 'static,
+    {
+        let id = HotkeyId(self.next_id);
+        self.next_id += 1;
+        self.registrations.push(Registration {
+            id,
+            hotkey,
+            trigger,
+            callback: Box::new(callback),
+        });
+        id
+    }
+
+    /// Returns false if `id` was not registered.
+    pub fn unregister(&mut self, id: HotkeyId) -> bool {
+        self.armed.retain(|armed| *armed != id);
+        let len = self.registrations.len();
+        self.registrations
+            .retain(|registration| registration.id != id);
+        self.registrations.len() != len
+    }
+
+    /// Calls the callbacks triggered by `event_type`. Returns whether the
+    /// event belongs to a registered hotkey, be it its press, repeats or
+    /// release.
+    pub fn handle(&mut self, event_type: &EventType) -> bool {
+        match event_type {
+            EventType::KeyPress(key) => {
+                let key = normalize(*key);
+                let repeat = !self.pressed.insert(key);
+                let mut matched = false;
+                for registration in &mut self.registrations {
+                    if !registration.hotkey.matches(key, &self.pressed) {
+                        continue;
+                    }
+                    matched = true;
+                    match registration.trigger {
+                        Trigger::Press if !repeat => (registration.callback)(),
+                        Trigger::Hold => (registration.callback)(),
+                        Trigger::Release if !self.armed.contains(&registration.id) => {
+                            self.armed.push(registration.id)
+                        }
+                        _ => {}
+                    }
+                }
+                if matched {
+                    self.triggered.insert(key);
+                }
+                matched
+            }
+            EventType::KeyRelease(key) => {
+                let key = normalize(*key);
+                self.pressed.remove(&key);
+                let armed = &mut self.armed;
+                for registration in &mut self.registrations {
+                    if registration.hotkey.key == key && armed.contains(&registration.id) {
+                        armed.retain(|armed| *armed != registration.id);
+                        (registration.callback)();
+                    }
+                }
+                self.triggered.remove(&key)
+            }
+            _ => false,
+        }
+    }
+
+    /// Listens to global events to trigger the hotkeys, see `listen`.
+    pub fn listen(mut self) -> Result<(), ListenError> {
+        crate::listen(move |event| {
+            self.handle(&event.event_type);
+        })
+    }
+
+    /// Grabs global events to trigger the hotkeys, swallowing the events of
+    /// the hotkeys so that other applications don't see them, see `grab`.
+    #[cfg(feature = "unstable_grab")]
+    pub fn grab(mut self) -> Result<(), GrabError> {
+        crate::grab(move |event| {
+            if self.handle(&event.event_type) {
+                None
+            } else {
+                Some(event)
+            }
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::cell::Cell;
+    use std::rc::Rc;
+
+    fn counter(manager: &mut HotkeyManager, hotkey: &str, trigger: Trigger) -> Rc<Cell<u32>> {
+        let count = Rc::new(Cell::new(0));
+        let callback_count = count.clone();
+        manager.register(hotkey.parse().unwrap(), trigger, move || {
+            callback_count.set(callback_count.get() + 1)
+        });
+        count
+    }
+
+    #[test]
+    fn test_parse() {
+        assert_eq!(
+            "Ctrl + Shift + K".parse(),
+            Ok(Hotkey::new([Key::ShiftRight, Key::ControlRight], Key::KeyK))
+        );
+        assert_eq!("f4".parse(), Ok(Hotkey::new(vec![], Key::F4)));
+        assert_eq!("ctrl+".parse::<Hotkey>(), Err(ParseHotkeyError::MissingKey));
+        assert_eq!(
+            "hyper+k".parse::<Hotkey>(),
+            Err(ParseHotkeyError::UnknownKey("hyper".to_string()))
+        );
+    }
+
+    #[test]
+    fn test_triggers() {
+        let mut manager = HotkeyManager::new();
+        let press = counter(&mut manager, "ctrl+shift+k", Trigger::Press);
+        let release = counter(&mut manager, "ctrl+shift+k", Trigger::Release);
+        let hold = counter(&mut manager, "ctrl+shift+k", Trigger::Hold);
+        let ctrl_k = counter(&mut manager, "ctrl+k", Trigger::Press);
+
+        assert!(!manager.handle(&EventType::KeyPress(Key::ControlRight)));
+        assert!(!manager.handle(&EventType::KeyPress(Key::ShiftLeft)));
+        assert!(manager.handle(&EventType::KeyPress(Key::KeyK)));
+        assert!(manager.handle(&EventType::KeyPress(Key::KeyK)));
+        assert_eq!((press.get(), release.get(), hold.get()), (1, 0, 2));
+        assert!(manager.handle(&EventType::KeyRelease(Key::KeyK)));
+        assert_eq!((press.get(), release.get(), hold.get()), (1, 1, 2));
+        // Extra modifiers don't match.
+        assert_eq!(ctrl_k.get(), 0);
+
+        manager.handle(&EventType::KeyRelease(Key::ShiftLeft));
+        assert!(manager.handle(&EventType::KeyPress(Key::KeyK)));
+        assert_eq!(ctrl_k.get(), 1);
+        assert!(!manager.handle(&EventType::KeyPress(Key::KeyL)));
+    }
+
+    #[test]
+    fn test_unregister() {
+        let mut manager = HotkeyManager::new();
+        let id = manager.register("alt+f4".parse().unwrap(), Trigger::Press, || {
+            panic!("Unregistered hotkey triggered")
+        });
+        assert!(manager.unregister(id));
+        assert!(!manager.unregister(id));
+        manager.handle(&EventType::KeyPress(Key::Alt));
+        assert!(!manager.handle(&EventType::KeyPress(Key::F4)));
+    }
+
+    #[test]
+    fn test_altgr() {
+        let mut manager = HotkeyManager::new();
+        let alt_q = counter(&mut manager, "alt+q", Trigger::Press);
+        let altgr_q = counter(&mut manager, "altgr+q", Trigger::Press);
+
+        // @ on German layouts
+        manager.handle(&EventType::KeyPress(Key::AltGr));
+        assert!(manager.handle(&EventType::KeyPress(Key::KeyQ)));
+        assert_eq!((alt_q.get(), altgr_q.get()), (0, 1));
+        manager.handle(&EventType::KeyRelease(Key::KeyQ));
+        manager.handle(&EventType::KeyRelease(Key::AltGr));
+
+        manager.handle(&EventType::KeyPress(Key::Alt));
+        assert!(manager.handle(&EventType::KeyPress(Key::KeyQ)));
+        assert_eq!((alt_q.get(), altgr_q.get()), (1, 1));
+    }
+}
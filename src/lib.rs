@@ -37,7 +37,123 @@
 //! `listen` calleback and will not trigger it with events. No error will be generated.
 //!
 //! ## Linux
-//! The `listen` function uses X11 APIs, and so will not work in Wayland or in the linux kernel virtual console
+//! The `listen` function uses X11 APIs by default, and so will not work in Wayland or in the
+//! Linux kernel virtual console
+//!
+//! With Xlib, dead keys and Compose sequences only come through `Event::name` once the
+//! program has set its locale, with `setlocale(LC_ALL, "")` from libc, before listening.
+//! rdev leaves the locale alone since it is global to the process.
+//!
+//! It reads events through the RECORD extension, or XInput2 raw events after
+//! `set_listen_backend(Some(Backend::XInput2))`. Those tell which device sent them
+//! and keep coming while another client grabs the keyboard, but held keys don't
+//! repeat, and the mouse position is asked to the X server after each motion.
+//!
+//! With the `unstable_grab` feature, `listen` reads the input devices directly when
+//! `DISPLAY` is unset, or after `set_listen_backend(Some(Backend::Evdev))`. This works
+//! in Wayland and in the virtual console, without grabbing anything, but needs read
+//! access to `/dev/input/event*`, usually by being in the `input` group. Without an
+//! X server, only x11rb gives events a name, and the mouse position is counted from
+//! the top left corner.
+//!
+//! `capabilities()` tells which backends `listen`, `grab` and `simulate` can use
+//! right now, and why the others can't: no `DISPLAY`, a missing X extension, or no
+//! permission on `/dev/input/event3` when the user isn't in the `input` group.
+//! `listen_with`, `grab_with` and `simulate_with` use the `Backend` they are given.
+//! On MacOS and Windows, there's only the platform's own backend to pick.
+//!
+//! ```rust
+//! for status in rdev::capabilities().listen {
+//!     if let Err(why) = status.status {
+//!         println!("{:?} can't listen: {}", status.backend, why);
+//!     }
+//! }
+//! ```
+//!
+//! With the `x11rb` feature, rdev talks to the X server through the pure Rust x11rb
+//! crate instead of libX11, and resolves key names with xkbcommon. It then only links
+//! against libxkbcommon, and `Keyboard` works from any thread.
+//!
+//! # Iterating over global events
+//!
+//! `events` runs the listener on a background thread and returns a blocking
+//! iterator, so the callback doesn't need to be `'static`. Dropping the
+//! iterator stops the listener. If the listener fails, the iterator ends and
+//! `Events::stop` returns the error.
+//!
+//! ```no_run
+//! use rdev::events;
+//! use std::time::Duration;
+//!
+//! let events = events().expect("Could not listen");
+//! while let Ok(event) = events.recv_timeout(Duration::from_secs(10)) {
+//!     println!("Received {:?}", event);
+//! }
+//! ```
+//!
+//! # Listening as an async stream (Requires `stream` feature)
+//!
+//! With the `stream` feature, `listen_stream` runs the listener on a background
+//! thread and hands out events as a `futures::Stream`. Dropping the stream stops
+//! the listener without blocking the executor. `listen_stream_bounded` caps the
+//! number of buffered events. If the listener fails, the stream ends and
+//! `EventStream::stop` returns the error.
+//!
+//! ```no_run
+//! # #[cfg(feature = "stream")]
+//! # async fn run() {
+//! use futures::StreamExt;
+//! use rdev::listen_stream;
+//!
+//! let mut events = listen_stream().expect("Could not listen");
+//! while let Some(event) = events.next().await {
+//!     println!("Received {:?}", event);
+//! }
+//! # }
+//! ```
+//!
+//! # Hotkeys
+//!
+//! `HotkeyManager` calls closures when hotkeys get pressed, released or held. Left and
+//! right modifiers are equivalent. With the `unstable_grab` feature, `HotkeyManager::grab`
+//! also hides the hotkeys from other applications.
+//!
+//! ```no_run
+//! use rdev::{Hotkey, HotkeyManager, Key, Trigger};
+//!
+//! let mut manager = HotkeyManager::new();
+//! manager.register(
+//!     Hotkey::new([Key::ControlLeft, Key::ShiftLeft], Key::KeyK),
+//!     Trigger::Press,
+//!     || println!("Ctrl+Shift+K"),
+//! );
+//! manager.register("alt+space".parse().unwrap(), Trigger::Release, || {
+//!     println!("Alt+Space")
+//! });
+//! // This will block.
+//! manager.listen().expect("Could not listen");
+//! ```
+//!
+//! # Key sequences
+//!
+//! `SequenceMatcher` detects multi-stroke bindings like "ctrl+x ctrl+s" or "space f f".
+//! Keys joined by `+` form a chord, pressed together in any order, and chords separated by
+//! spaces must follow each other within the timeout. `SequenceMatcher::pending` lists the
+//! chords typed so far.
+//!
+//! ```no_run
+//! use rdev::{listen, SequenceMatch, SequenceMatcher};
+//! use std::time::Duration;
+//!
+//! let mut matcher = SequenceMatcher::new(Duration::from_secs(1));
+//! let save = matcher.register("ctrl+x ctrl+s".parse().unwrap()).unwrap();
+//! listen(move |event| {
+//!     if matcher.handle(&event.event_type) == SequenceMatch::Complete(save) {
+//!         println!("Save");
+//!     }
+//! })
+//! .expect("Could not listen");
+//! ```
 //!
 //! # Sending some events
 //!
@@ -69,6 +185,38 @@
 //!     delta_y: 1,
 //! });
 //! ```
+//!
+//! To send many events, `Simulator` keeps its connection to the OS open (on Linux,
+//! `simulate` opens a new X connection for every event).
+//!
+//! ```no_run
+//! use rdev::{EventType, Key, Simulator};
+//!
+//! let mut simulator = Simulator::new().unwrap();
+//! simulator
+//!     .send_batch(&[
+//!         EventType::KeyPress(Key::KeyS),
+//!         EventType::KeyRelease(Key::KeyS),
+//!     ])
+//!     .unwrap();
+//! // Waits for the events to be processed.
+//! simulator.flush().unwrap();
+//! ```
+//!
+//! On Linux, `simulate_text` types any Unicode text, whatever the keyboard layout.
+//!
+//! ```no_run
+//! # #[cfg(target_os = "linux")]
+//! rdev::simulate_text("Größe: 10 €").unwrap();
+//! ```
+//!
+//! On Linux, with the `unstable_grab` feature, `simulate` sends events through virtual
+//! devices created with `/dev/uinput` when `DISPLAY` is unset, or after
+//! `set_simulate_backend(Some(Backend::Uinput))`. They work in Wayland and in the
+//! virtual console, and stay around until the process exits. Mouse moves need the
+//! screen size, from the X server or the framebuffer, and `Simulator::move_by` moves
+//! the pointer relatively. `simulate_text` still needs XTest.
+//!
 //! # Main structs
 //! ## Event
 //!
@@ -80,7 +228,7 @@
 //! what key was interpreted by the OS at that time, it will respect the layout.
 //!
 //! ```no_run
-//! # use crate::rdev::EventType;
+//! # use crate::rdev::{DeviceInfo, EventType, Modifiers};
 //! # use std::time::SystemTime;
 //! /// When events arrive from the system we can add some information
 //! /// time is when the event was received.
@@ -89,13 +237,23 @@
 //!     pub time: SystemTime,
 //!     pub name: Option<String>,
 //!     pub event_type: EventType,
+//!     pub modifiers: Modifiers,
+//!     pub device: Option<DeviceInfo>,
 //! }
 //! ```
 //!
+//! `Event::modifiers` tells which modifiers (shift, ctrl, alt, altgr, meta) were held
+//! and which locks were on, including the change made by the event's own key.
+//! On MacOS they come from the event's flags, which have no altgr nor num lock.
+//! `Event::device` tells which keyboard or mouse sent the event, with its name and
+//! vendor/product ID. Only the Linux `grab`, and `listen` with XInput2 or evdev, fill
+//! it in for now, it's None elsewhere.
+//!
 //! Be careful, Event::name, might be None, but also String::from(""), and might contain
 //! not displayable unicode characters. We send exactly what the OS sends us so do some sanity checking
 //! before using it.
-//! Caveat: Dead keys don't function yet on Linux
+//! Caveat: Dead keys only function on Linux (Xlib) once the program set its locale,
+//! with `setlocale(LC_ALL, "")`.
 //!
 //! ## EventType
 //!
@@ -153,9 +311,11 @@
 //! layout for now !
 //! Caveat : This is layout dependent. If your app needs to support
 //! layout switching don't use this !
-//! Caveat: On Linux, the dead keys mechanism is not implemented.
-//! Caveat: Only shift and dead keys are implemented, Alt+unicode code on windows
-//! won't work.
+//! Caveat: On Linux (Xlib), the dead keys mechanism needs the program to set its
+//! locale first, with `setlocale(LC_ALL, "")`.
+//! Caveat: On Linux, Shift, CapsLock, Alt, AltGr, Meta and Control are tracked, all
+//! but Control change the name. Elsewhere, only shift and dead keys are implemented,
+//! Alt+Unicode code on Windows won't work.
 //!
 //! ```no_run
 //! use rdev::{Keyboard, EventType, Key, KeyboardState};
@@ -172,7 +332,15 @@
 //! by suppling this function with a callback, you can intercept
 //! all keyboard and mouse events before they are delivered to applications / window managers.
 //! In the callback, returning None ignores the event and returning the event let's it pass.
-//! There is no modification of the event possible here (yet).
+//! On Linux, returning a modified event sends it instead of the original one.
+//! `grab_emit` goes further and lets the callback return any number of events
+//! to send in place of each one.
+//! The callback can keep state between events, and on Linux `grab_scoped` takes a
+//! callback borrowing local variables.
+//! `grab_with_handle` grabs on a thread of its own and returns a `GrabHandle`,
+//! whose `stop` ungrabs every device and hands them back to the system.
+//! On Linux, a `GrabConfig` selects which devices get grabbed, by name, phys path,
+//! vendor:product ID or capability, and `list_devices` lists them to choose from.
 //!
 //! Note: the use of the word `unstable` here refers specifically to the fact that the `grab` API is unstable and subject to change
 //!
@@ -195,6 +363,29 @@
 //! }
 //! ```
 //!
+//! On Linux, `Remapper` turns `grab` into a keyd/kmonad-style remapper: key swaps, tap-hold
+//! keys, layers toggled by a key and macros sending several events.
+//!
+//! ```no_run
+//! use rdev::{Action, Key, Remapper};
+//!
+//! let mut remapper = Remapper::new();
+//! // CapsLock is Escape when tapped, Control when held.
+//! remapper.map(
+//!     Key::CapsLock,
+//!     Action::TapHold {
+//!         tap: Key::Escape,
+//!         hold: Key::ControlLeft,
+//!     },
+//! );
+//! let navigation = remapper.add_layer();
+//! remapper.map(Key::ScrollLock, Action::ToggleLayer(navigation));
+//! remapper.map_in(navigation, Key::KeyH, Action::Key(Key::LeftArrow));
+//! // This will block.
+//! #[cfg(all(feature = "unstable_grab", target_os = "linux"))]
+//! remapper.grab().expect("Could not grab");
+//! ```
+//!
 //! ## OS Caveats:
 //! When using the `listen` and/or `grab` functions, the following caveats apply:
 //!
@@ -217,36 +408,76 @@
 //!
 //! Event data returned by the `listen` and `grab` functions can be serialized and de-serialized with
 //! Serde if you install this library with the `serialize` feature.
+mod device;
+mod events;
+mod hotkey;
 mod rdev;
+mod remap;
+mod sequence;
 pub use crate::rdev::{
-    Button, DisplayError, Event, EventType, GrabCallback, GrabError, Key, KeyboardState,
-    ListenError, SimulateError,
+    Backend, BackendStatus, Button, Capabilities, DeviceInfo, DisplayError, Event, EventType,
+    GrabCallback, GrabError, Key, KeyboardState, ListenError, Modifiers, ParseHotkeyError,
+    SimulateError, Unavailable,
 };
 
+pub use crate::device::{DeviceFilter, GrabConfig};
+pub use crate::events::{events, Events};
+pub use crate::hotkey::{Hotkey, HotkeyId, HotkeyManager, Trigger};
+pub use crate::remap::{Action, Layer, Remapper};
+pub use crate::sequence::{Chord, Sequence, SequenceId, SequenceMatch, SequenceMatcher};
+
+#[cfg(feature = "stream")]
+mod stream;
+#[cfg(feature = "stream")]
+pub use crate::stream::{listen_stream, listen_stream_bounded, EventStream, OverflowPolicy};
+
+#[cfg(any(target_os = "macos", target_os = "windows"))]
+mod native;
+#[cfg(feature = "unstable_grab")]
+#[cfg(any(target_os = "macos", target_os = "windows"))]
+pub use crate::native::grab_with;
+#[cfg(any(target_os = "macos", target_os = "windows"))]
+pub use crate::native::{capabilities, listen_with, simulate_with};
+
 #[cfg(target_os = "macos")]
 mod macos;
 #[cfg(target_os = "macos")]
-pub use crate::macos::Keyboard;
+use crate::macos::{
+    display_size as _display_size, listen as _listen, listen_with_handle as _listen_with_handle,
+    simulate as _simulate,
+};
 #[cfg(target_os = "macos")]
-use crate::macos::{display_size as _display_size, listen as _listen, simulate as _simulate};
+pub use crate::macos::{Keyboard, ListenHandle, Simulator};
 
 #[cfg(target_os = "linux")]
 mod linux;
 #[cfg(target_os = "linux")]
-pub use crate::linux::Keyboard;
+pub use crate::linux::{
+    capabilities, listen_with, set_listen_backend, set_simulate_backend, simulate_text,
+    simulate_with, Keyboard, ListenHandle, Simulator,
+};
 #[cfg(target_os = "linux")]
-use crate::linux::{display_size as _display_size, listen as _listen, simulate as _simulate};
+use crate::linux::{
+    display_size as _display_size, listen as _listen, listen_with_handle as _listen_with_handle,
+    simulate as _simulate,
+};
 
 #[cfg(target_os = "windows")]
 mod windows;
 #[cfg(target_os = "windows")]
-pub use crate::windows::Keyboard;
+use crate::windows::{
+    display_size as _display_size, listen as _listen, listen_with_handle as _listen_with_handle,
+    simulate as _simulate,
+};
 #[cfg(target_os = "windows")]
-use crate::windows::{display_size as _display_size, listen as _listen, simulate as _simulate};
+pub use crate::windows::{Keyboard, ListenHandle, Simulator};
 
 /// Listening to global events. Caveat: On MacOS, you require the listen
 /// loop needs to be the primary app (no fork before) and need to have accessibility
 /// settings enabled.
+/// On Linux, `listen` can be called from several threads at once, every
+/// callback receives all events. With Xlib, dead keys only show up in
+/// `Event::name` once the program has called `setlocale(LC_ALL, "")`.
 ///
 /// ```no_run
 /// use rdev::{listen, Event};
@@ -272,6 +503,32 @@ where
     _listen(callback)
 }
 
+/// Listening to global events from a background thread. Unlike `listen`, this
+/// returns as soon as the listener is set up, with a `ListenHandle` that can
+/// stop it later on. Once stopped, listening can be started again.
+/// Caveat: On MacOS, you need to have accessibility settings enabled.
+///
+/// ```no_run
+/// use rdev::{listen_with_handle, Event};
+/// use std::{thread, time};
+///
+/// fn callback(event: Event) {
+///     println!("My callback {:?}", event);
+/// }
+/// fn main(){
+///     let handle = listen_with_handle(callback).expect("Could not listen");
+///     thread::sleep(time::Duration::from_secs(5));
+///     // Waits for the listener to be torn down.
+///     handle.stop().expect("Could not stop listening");
+/// }
+/// ```
+pub fn listen_with_handle<T>(callback: T) -> Result<ListenHandle, ListenError>
+where
+    T: FnMut(Event) + Send + 'static,
+{
+    _listen_with_handle(callback)
+}
+
 /// Sending some events
 ///
 /// ```no_run
@@ -325,6 +582,9 @@ pub fn display_size() -> Result<(u64, u64), DisplayError> {
 #[cfg(target_os = "linux")]
 pub use crate::linux::grab as _grab;
 #[cfg(feature = "unstable_grab")]
+#[cfg(target_os = "linux")]
+pub use crate::linux::{grab_emit, grab_with, grab_with_handle, list_devices, GrabHandle};
+#[cfg(feature = "unstable_grab")]
 #[cfg(target_os = "macos")]
 pub use crate::macos::grab as _grab;
 #[cfg(feature = "unstable_grab")]
@@ -332,8 +592,8 @@ pub use crate::macos::grab as _grab;
 pub use crate::windows::grab as _grab;
 #[cfg(any(feature = "unstable_grab"))]
 /// Grabbing global events. In the callback, returning None ignores the event
-/// and returning the event let's it pass. There is no modification of the event
-/// possible here.
+/// and returning the event let's it pass. On Linux, returning a modified event
+/// sends it instead, unless evdev can't represent it.
 /// Caveat: On MacOS, you require the grab
 /// loop needs to be the primary app (no fork before) and need to have accessibility
 /// settings enabled.
@@ -359,7 +619,33 @@ pub use crate::windows::grab as _grab;
 #[cfg(any(feature = "unstable_grab"))]
 pub fn grab<T>(callback: T) -> Result<(), GrabError>
 where
-    T: Fn(Event) -> Option<Event> + 'static,
+    T: FnMut(Event) -> Option<Event> + 'static,
+{
+    _grab(callback)
+}
+
+/// Same as `grab`, for a callback borrowing from the caller. The grab loop
+/// runs on the calling thread, so the callback can hold on to local state
+/// without a `Mutex` or `RefCell`.
+///
+/// ```no_run
+/// use rdev::{grab_scoped, EventType, Key};
+///
+/// let mut tabs = 0;
+/// // This will block.
+/// grab_scoped(|event| {
+///     if let EventType::KeyPress(Key::Tab) = event.event_type {
+///         tabs += 1;
+///     }
+///     Some(event)
+/// })
+/// .expect("Could not grab");
+/// ```
+#[cfg(feature = "unstable_grab")]
+#[cfg(target_os = "linux")]
+pub fn grab_scoped<T>(callback: T) -> Result<(), GrabError>
+where
+    T: FnMut(Event) -> Option<Event>,
 {
     _grab(callback)
 }
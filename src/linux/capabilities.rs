@@ -0,0 +1,216 @@
+use crate::linux::backend::Display;
+use crate::linux::common::has_display;
+#[cfg(feature = "unstable_grab")]
+use crate::linux::grab::{get_device_paths, DEV_PATH};
+use crate::rdev::{Backend, BackendStatus, Capabilities, Unavailable};
+use std::ffi::CStr;
+#[cfg(feature = "unstable_grab")]
+use std::fs::File;
+use std::fs::{self, OpenOptions};
+use std::io;
+use std::mem;
+use std::os::raw::c_char;
+use std::os::unix::fs::MetadataExt;
+use std::path::Path;
+#[cfg(feature = "unstable_grab")]
+use std::path::PathBuf;
+use std::ptr;
+
+static UINPUT_PATH: &str = "/dev/uinput";
+
+/// Tells which backends `listen`, `grab` and `simulate` can use right now,
+/// and why the others can't.
+///
+/// ```no_run
+/// use rdev::capabilities;
+///
+/// for status in capabilities().listen {
+///     match status.status {
+///         Ok(()) => println!("{:?} can listen", status.backend),
+///         Err(why) => println!("{:?} can't listen: {}", status.backend, why),
+///     }
+/// }
+/// ```
+pub fn capabilities() -> Capabilities {
+    // A single short-lived connection, asked which extensions it has.
+    let display = display();
+    let extension = |name, present: fn(&Display) -> bool| match &display {
+        Ok(display) if present(display) => Ok(()),
+        Ok(_) => Err(Unavailable::MissingExtension(name)),
+        Err(why) => Err(why.clone()),
+    };
+    let evdev = feature().and_then(|_| input_devices());
+    let uinput = feature().and_then(|_| uinput());
+    Capabilities {
+        listen: vec![
+            status(Backend::Record, extension("RECORD", Display::has_record)),
+            status(
+                Backend::XInput2,
+                extension("XInputExtension 2.1", Display::has_xinput2),
+            ),
+            status(Backend::Evdev, evdev.clone()),
+        ],
+        // Grabbing sends the events through uinput clones, and asks the X
+        // server where the pointer is.
+        grab: vec![status(
+            Backend::Evdev,
+            evdev
+                .and(uinput.clone())
+                .and(display.as_ref().map(|_| ()).map_err(Clone::clone)),
+        )],
+        simulate: vec![
+            status(Backend::XTest, extension("XTEST", Display::has_xtest)),
+            status(Backend::Uinput, uinput),
+        ],
+    }
+}
+
+fn status(backend: Backend, status: Result<(), Unavailable>) -> BackendStatus {
+    BackendStatus { backend, status }
+}
+
+fn display() -> Result<Display, Unavailable> {
+    if !has_display() {
+        return Err(Unavailable::NoDisplay);
+    }
+    Display::new().ok_or(Unavailable::DisplayUnreachable)
+}
+
+/// evdev and uinput come with the `unstable_grab` feature.
+fn feature() -> Result<(), Unavailable> {
+    if cfg!(feature = "unstable_grab") {
+        Ok(())
+    } else {
+        Err(Unavailable::MissingFeature("unstable_grab"))
+    }
+}
+
+/// Fine as long as one input device can be read.
+#[cfg(feature = "unstable_grab")]
+fn input_devices() -> Result<(), Unavailable> {
+    let paths =
+        get_device_paths(DEV_PATH).map_err(|error| io_unavailable(Path::new(DEV_PATH), error))?;
+    let mut denied = None;
+    for path in paths {
+        match File::open(&path) {
+            Ok(_) => return Ok(()),
+            Err(error) => {
+                denied.get_or_insert(io_unavailable(&path, error));
+            }
+        }
+    }
+    Err(denied.unwrap_or_else(|| Unavailable::MissingDevice(PathBuf::from(DEV_PATH))))
+}
+
+#[cfg(not(feature = "unstable_grab"))]
+fn input_devices() -> Result<(), Unavailable> {
+    feature()
+}
+
+fn uinput() -> Result<(), Unavailable> {
+    OpenOptions::new()
+        .write(true)
+        .open(UINPUT_PATH)
+        .map(|_| ())
+        .map_err(|error| io_unavailable(Path::new(UINPUT_PATH), error))
+}
+
+fn io_unavailable(path: &Path, error: io::Error) -> Unavailable {
+    match error.kind() {
+        io::ErrorKind::NotFound => Unavailable::MissingDevice(path.to_path_buf()),
+        io::ErrorKind::PermissionDenied => Unavailable::PermissionDenied {
+            path: path.to_path_buf(),
+            group: foreign_group(path),
+        },
+        _ => Unavailable::Error(format!("{}: {}", path.display(), error)),
+    }
+}
+
+/// The group owning `path`, unless the user is part of it.
+fn foreign_group(path: &Path) -> Option<String> {
+    let gid = fs::metadata(path).ok()?.gid();
+    if user_groups().contains(&gid) {
+        return None;
+    }
+    group_name(gid)
+}
+
+fn user_groups() -> Vec<libc::gid_t> {
+    unsafe {
+        let count = libc::getgroups(0, ptr::null_mut());
+        let mut groups = vec![0; count.max(0) as usize];
+        let count = libc::getgroups(count, groups.as_mut_ptr());
+        groups.truncate(count.max(0) as usize);
+        groups.push(libc::getegid());
+        groups
+    }
+}
+
+fn group_name(gid: libc::gid_t) -> Option<String> {
+    let mut group: libc::group = unsafe { mem::zeroed() };
+    let mut buffer = vec![0 as c_char; 4096];
+    let mut result = ptr::null_mut();
+    let status = unsafe {
+        libc::getgrgid_r(
+            gid,
+            &mut group,
+            buffer.as_mut_ptr(),
+            buffer.len(),
+            &mut result,
+        )
+    };
+    if status != 0 || result.is_null() {
+        return None;
+    }
+    let name = unsafe { CStr::from_ptr(group.gr_name) };
+    Some(name.to_string_lossy().into_owned())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::path::PathBuf;
+
+    #[test]
+    fn test_capabilities() {
+        let capabilities = capabilities();
+        let backends = |statuses: &[BackendStatus]| {
+            statuses
+                .iter()
+                .map(|status| status.backend)
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(
+            backends(&capabilities.listen),
+            [Backend::Record, Backend::XInput2, Backend::Evdev]
+        );
+        assert_eq!(backends(&capabilities.grab), [Backend::Evdev]);
+        assert_eq!(
+            backends(&capabilities.simulate),
+            [Backend::XTest, Backend::Uinput]
+        );
+        if cfg!(not(feature = "unstable_grab")) {
+            assert_eq!(
+                capabilities.listen[2].status,
+                Err(Unavailable::MissingFeature("unstable_grab"))
+            );
+        }
+    }
+
+    #[test]
+    fn test_unavailable_display() {
+        let denied = Unavailable::PermissionDenied {
+            path: PathBuf::from("/dev/input/event3"),
+            group: Some("input".to_string()),
+        };
+        assert_eq!(
+            denied.to_string(),
+            "permission denied on /dev/input/event3 (user not in input group)"
+        );
+        assert_eq!(Unavailable::NoDisplay.to_string(), "no DISPLAY");
+        assert_eq!(
+            Unavailable::MissingExtension("RECORD").to_string(),
+            "RECORD extension missing"
+        );
+    }
+}
@@ -1,18 +1,12 @@
-use crate::linux::keyboard::Keyboard;
+use crate::linux::backend::Keyboard;
 use crate::linux::keycodes::key_from_code;
-use crate::rdev::{Button, Event, EventType, KeyboardState};
-use std::convert::TryInto;
-use std::os::raw::{c_int, c_uchar, c_uint};
-use std::ptr::null;
+use crate::rdev::{Button, Event, EventType, KeyboardState, Modifiers};
+use std::io;
+use std::os::raw::{c_int, c_uchar, c_uint, c_void};
+use std::os::unix::io::{AsRawFd, RawFd};
 use std::time::SystemTime;
 use x11::xlib;
 
-pub const TRUE: c_int = 1;
-pub const FALSE: c_int = 0;
-
-// A global for the callbacks.
-pub static mut KEYBOARD: Option<Keyboard> = None;
-
 pub fn convert_event(code: c_uchar, type_: c_int, x: f64, y: f64) -> Option<EventType> {
     match type_ {
         xlib::KeyPress => {
@@ -51,82 +45,111 @@ pub fn convert_event(code: c_uchar, type_: c_int, x: f64, y: f64) -> Option<Even
     }
 }
 
+/// Modifiers from a core protocol state mask, which holds them from before
+/// the event. Mod1 to Mod5 are bound the way XKB usually binds them.
+pub fn modifiers_from_state(state: c_uint) -> Modifiers {
+    let masks = [
+        (xlib::ShiftMask, Modifiers::SHIFT),
+        (xlib::LockMask, Modifiers::CAPS_LOCK),
+        (xlib::ControlMask, Modifiers::CTRL),
+        (xlib::Mod1Mask, Modifiers::ALT),
+        (xlib::Mod2Mask, Modifiers::NUM_LOCK),
+        (xlib::Mod4Mask, Modifiers::META),
+        (xlib::Mod5Mask, Modifiers::ALT_GR),
+    ];
+    masks
+        .iter()
+        .filter(|(mask, _)| state & mask != 0)
+        .fold(Modifiers::empty(), |modifiers, (_, modifier)| {
+            modifiers | *modifier
+        })
+}
+
 pub fn convert(
-    keyboard: &mut Option<Keyboard>,
+    keyboard: &mut Keyboard,
     code: c_uint,
     type_: c_int,
     x: f64,
     y: f64,
+    state: c_uint,
 ) -> Option<Event> {
     let event_type = convert_event(code as c_uchar, type_, x, y)?;
-    let kb: &mut Keyboard = (*keyboard).as_mut()?;
-    let name = kb.add(&event_type);
+    let name = keyboard.add(&event_type);
+    let mut modifiers = modifiers_from_state(state);
+    modifiers.update(&event_type);
     Some(Event {
         event_type,
         time: SystemTime::now(),
         name,
+        modifiers,
+        device: None,
     })
 }
 
-pub struct Display {
-    display: *mut xlib::Display,
+/// Whether an X server is around, Wayland sessions usually run Xwayland which
+/// sets `DISPLAY` too.
+pub fn has_display() -> bool {
+    std::env::var_os("DISPLAY").is_some_and(|display| !display.is_empty())
 }
 
-impl Display {
-    pub fn new() -> Option<Display> {
-        unsafe {
-            let display = xlib::XOpenDisplay(null());
-            if display.is_null() {
-                return None;
-            }
-            Some(Display { display })
+/// Thin wrapper around an eventfd, used to wake up a blocking loop from another
+/// thread.
+pub struct EventFd(RawFd);
+
+impl EventFd {
+    pub fn new() -> io::Result<EventFd> {
+        let fd = unsafe { libc::eventfd(0, libc::EFD_CLOEXEC) };
+        if fd < 0 {
+            return Err(io::Error::last_os_error());
         }
+        Ok(EventFd(fd))
     }
 
-    pub fn get_size(&self) -> Option<(u64, u64)> {
-        unsafe {
-            let screen_ptr = xlib::XDefaultScreenOfDisplay(self.display);
-            if screen_ptr.is_null() {
-                return None;
-            }
-            let screen = *screen_ptr;
-            Some((
-                screen.width.try_into().ok()?,
-                screen.height.try_into().ok()?,
-            ))
+    pub fn notify(&self) -> io::Result<()> {
+        let value: u64 = 1;
+        let written = unsafe {
+            libc::write(
+                self.0,
+                &value as *const u64 as *const c_void,
+                std::mem::size_of::<u64>(),
+            )
+        };
+        if written < 0 {
+            return Err(io::Error::last_os_error());
         }
+        Ok(())
     }
+}
 
-    #[cfg(feature = "unstable_grab")]
-    pub fn get_mouse_pos(&self) -> Option<(u64, u64)> {
-        unsafe {
-            let root_window = xlib::XRootWindow(self.display, 0);
-            let mut root_x = 0;
-            let mut root_y = 0;
-            let mut x = 0;
-            let mut y = 0;
-            let mut root = 0;
-            let mut child = 0;
-            let mut mask = 0;
-            let _screen_ptr = xlib::XQueryPointer(
-                self.display,
-                root_window,
-                &mut root,
-                &mut child,
-                &mut root_x,
-                &mut root_y,
-                &mut x,
-                &mut y,
-                &mut mask,
-            );
-            Some((root_x.try_into().ok()?, root_y.try_into().ok()?))
-        }
+impl AsRawFd for EventFd {
+    fn as_raw_fd(&self) -> RawFd {
+        self.0
     }
 }
-impl Drop for Display {
+
+impl Drop for EventFd {
     fn drop(&mut self) {
         unsafe {
-            xlib::XCloseDisplay(self.display);
+            libc::close(self.0);
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::rdev::Key;
+
+    #[test]
+    fn test_modifiers() {
+        let mut modifiers = modifiers_from_state(xlib::ShiftMask | xlib::Mod2Mask);
+        assert_eq!(modifiers, Modifiers::SHIFT | Modifiers::NUM_LOCK);
+        modifiers.update(&EventType::KeyPress(Key::AltGr));
+        modifiers.update(&EventType::KeyRelease(Key::ShiftLeft));
+        modifiers.update(&EventType::KeyPress(Key::NumLock));
+        assert_eq!(modifiers, Modifiers::ALT_GR);
+        modifiers.update(&EventType::KeyPress(Key::CapsLock));
+        modifiers.update(&EventType::KeyRelease(Key::CapsLock));
+        assert_eq!(modifiers, Modifiers::ALT_GR | Modifiers::CAPS_LOCK);
+    }
+}
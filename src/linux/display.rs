@@ -1,4 +1,4 @@
-use crate::linux::common::Display;
+use crate::linux::backend::Display;
 use crate::rdev::DisplayError;
 
 pub fn display_size() -> Result<(u64, u64), DisplayError> {
@@ -1,26 +1,34 @@
-use crate::linux::common::Display;
-use crate::linux::keyboard::Keyboard;
-use crate::rdev::{Button, Event, EventType, GrabError, Key, KeyboardState};
+use crate::device::GrabConfig;
+use crate::linux::backend::{Display, Keyboard};
+use crate::linux::common::EventFd;
+use crate::rdev::{
+    Backend, Button, DeviceInfo, Event, EventType, GrabError, Key, KeyboardState, ListenError,
+    Modifiers,
+};
+use crate::remap::Remapper;
 use epoll::ControlOptions::{EPOLL_CTL_ADD, EPOLL_CTL_DEL};
 use evdev_rs::{
-    enums::{EventCode, EV_KEY, EV_REL},
-    Device, InputEvent, UInputDevice,
+    enums::{int_to_ev_key, EventCode, InputProp, EV_ABS, EV_KEY, EV_REL, EV_SYN},
+    Device, InputEvent, ReadFlag, ReadStatus, TimeVal, UInputDevice,
 };
-use inotify::{Inotify, WatchMask};
-use std::ffi::{OsStr, OsString};
-use std::fs::{read_dir, File};
+use inotify::{EventMask, Inotify, WatchMask};
+use std::convert::TryInto;
+use std::ffi::OsStr;
+use std::fs::{read_dir, read_link, read_to_string, File};
 use std::io;
+use std::os::raw::c_ulong;
 use std::os::unix::{
     ffi::OsStrExt,
     fs::FileTypeExt,
     io::{AsRawFd, IntoRawFd, RawFd},
 };
-use std::path::Path;
+use std::panic;
+use std::path::{Path, PathBuf};
+use std::sync::mpsc::sync_channel;
+use std::sync::Arc;
+use std::thread::{self, JoinHandle};
 use std::time::SystemTime;
 
-// TODO The x, y coordinates are currently wrong !! Is there mouse acceleration
-// to take into account ??
-
 macro_rules! convert_keys {
     ($($ev_key:ident, $rdev_key:ident),*) => {
         //TODO: make const when rust lang issue #49146 is fixed
@@ -34,15 +42,16 @@ macro_rules! convert_keys {
             }
         }
 
-        // //TODO: make const when rust lang issue #49146 is fixed
-        // fn rdev_key_to_evdev_key(key: &Key) -> Option<EV_KEY> {
-        //     match key {
-        //         $(
-        //             Key::$rdev_key => Some(EV_KEY::$ev_key),
-        //         )*
-        //         _ => None
-        //     }
-        // }
+        //TODO: make const when rust lang issue #49146 is fixed
+        #[allow(unreachable_patterns)]
+        fn rdev_key_to_evdev_key(key: &Key) -> Option<EV_KEY> {
+            match key {
+                $(
+                    Key::$rdev_key => Some(EV_KEY::$ev_key),
+                )*
+                _ => None
+            }
+        }
     };
 }
 
@@ -58,15 +67,15 @@ macro_rules! convert_buttons {
             }
         }
 
-        // //TODO: make const when rust lang issue #49146 is fixed
-        // fn rdev_button_to_evdev_key(event: &Button) -> Option<EV_KEY> {
-        //     match event {
-        //         $(
-        //             Button::$rdev_key => Some(EV_KEY::$ev_key),
-        //         )*
-        //         _ => None
-        //     }
-        // }
+        //TODO: make const when rust lang issue #49146 is fixed
+        fn rdev_button_to_evdev_key(event: &Button) -> Option<EV_KEY> {
+            match event {
+                $(
+                    Button::$rdev_key => Some(EV_KEY::$ev_key),
+                )*
+                _ => None
+            }
+        }
     };
 }
 
@@ -188,12 +197,56 @@ convert_keys!(
     KEY_BACKSLASH, IntlBackslash
 );
 
+/// Absolute devices, like tablets, touchscreens or the pointer of a VM, map
+/// their ABS_X and ABS_Y ranges onto the whole screen.
+#[derive(Debug, Clone, Copy)]
+struct AbsAxes {
+    x: (i32, i32),
+    y: (i32, i32),
+}
+
+impl AbsAxes {
+    fn new(device: &Device) -> Option<AbsAxes> {
+        // Touchpads move the pointer relatively, and joysticks don't move it.
+        if device.has_property(&InputProp::INPUT_PROP_POINTER)
+            || !(device.has_event_code(&EventCode::EV_KEY(EV_KEY::BTN_LEFT))
+                || device.has_event_code(&EventCode::EV_KEY(EV_KEY::BTN_TOUCH)))
+        {
+            return None;
+        }
+        let x = device.abs_info(&EventCode::EV_ABS(EV_ABS::ABS_X))?;
+        let y = device.abs_info(&EventCode::EV_ABS(EV_ABS::ABS_Y))?;
+        Some(AbsAxes {
+            x: (x.minimum, x.maximum),
+            y: (y.minimum, y.maximum),
+        })
+    }
+}
+
+/// Maps `value` from the axis `range` onto `size` pixels.
+fn abs_to_screen(value: i32, range: (i32, i32), size: f64) -> f64 {
+    let (minimum, maximum) = range;
+    if maximum <= minimum {
+        return 0.0;
+    }
+    (value - minimum) as f64 * size / (maximum - minimum) as f64
+}
+
+fn screen_to_abs(position: f64, range: (i32, i32), size: f64) -> i32 {
+    let (minimum, maximum) = range;
+    if size <= 0.0 {
+        return minimum;
+    }
+    minimum + (position * (maximum - minimum) as f64 / size).round() as i32
+}
+
 fn evdev_event_to_rdev_event(
     event: &InputEvent,
     x: &mut f64,
     y: &mut f64,
     w: f64,
     h: f64,
+    abs: Option<&AbsAxes>,
 ) -> Option<EventType> {
     match &event.event_code {
         EventCode::EV_KEY(key) => {
@@ -248,168 +301,813 @@ fn evdev_event_to_rdev_event(
             // Other EV_REL events cannot be represented by rdev
             _ => None,
         },
+        EventCode::EV_ABS(axis) => {
+            let abs = abs?;
+            match axis {
+                EV_ABS::ABS_X => *x = abs_to_screen(event.value, abs.x, w),
+                EV_ABS::ABS_Y => *y = abs_to_screen(event.value, abs.y, h),
+                // Pressure, tilt, multitouch slots...
+                _ => return None,
+            }
+            Some(EventType::MouseMove { x: *x, y: *y })
+        }
         // Other event_codes cannot be represented by rdev,
         // and some never will e.g. EV_SYN
         _ => None,
     }
 }
 
-// fn rdev_event_to_evdev_event(event: &EventType, time: &TimeVal) -> Option<InputEvent> {
-//     match event {
-//         EventType::KeyPress(key) => {
-//             let key = rdev_key_to_evdev_key(&key)?;
-//             Some(InputEvent::new(&time, &EventCode::EV_KEY(key), 1))
-//         }
-//         EventType::KeyRelease(key) => {
-//             let key = rdev_key_to_evdev_key(&key)?;
-//             Some(InputEvent::new(&time, &EventCode::EV_KEY(key), 0))
-//         }
-//         EventType::ButtonPress(button) => {
-//             let button = rdev_button_to_evdev_key(&button)?;
-//             Some(InputEvent::new(&time, &EventCode::EV_KEY(button), 1))
-//         }
-//         EventType::ButtonRelease(button) => {
-//             let button = rdev_button_to_evdev_key(&button)?;
-//             Some(InputEvent::new(&time, &EventCode::EV_KEY(button), 0))
-//         }
-//         EventType::MouseMove { x, y } => {
-//             let (x, y) = (*x as i32, *y as i32);
-//             //TODO allow both x and y movements simultaneously
-//             if x != 0 {
-//                 Some(InputEvent::new(&time, &EventCode::EV_REL(EV_REL::REL_X), x))
-//             } else {
-//                 Some(InputEvent::new(&time, &EventCode::EV_REL(EV_REL::REL_Y), y))
-//             }
-//         }
-//         EventType::Wheel { delta_x, delta_y } => {
-//             let (x, y) = (*delta_x as i32, *delta_y as i32);
-//             //TODO allow both x and y movements simultaneously
-//             if x != 0 {
-//                 Some(InputEvent::new(
-//                     &time,
-//                     &EventCode::EV_REL(EV_REL::REL_HWHEEL),
-//                     x,
-//                 ))
-//             } else {
-//                 Some(InputEvent::new(
-//                     &time,
-//                     &EventCode::EV_REL(EV_REL::REL_WHEEL),
-//                     y,
-//                 ))
-//             }
-//         }
-//     }
-// }
+/// Every key and button rdev can send.
+pub fn rdev_keys() -> impl Iterator<Item = EV_KEY> {
+    (0..=EV_KEY::KEY_MAX as u32)
+        .filter_map(int_to_ev_key)
+        .filter(|key| {
+            evdev_key_to_rdev_key(key).is_some() || evdev_key_to_rdev_button(key).is_some()
+        })
+}
+
+/// Pointer moves can't be converted on their own: rdev has absolute positions
+/// where evdev has relative moves, see `rdev_move_to_evdev_events`.
+pub fn rdev_event_to_evdev_event(event: &EventType, time: &TimeVal) -> Option<InputEvent> {
+    match event {
+        EventType::KeyPress(key) => {
+            let key = rdev_key_to_evdev_key(key)?;
+            Some(InputEvent::new(time, &EventCode::EV_KEY(key), 1))
+        }
+        EventType::KeyRelease(key) => {
+            let key = rdev_key_to_evdev_key(key)?;
+            Some(InputEvent::new(time, &EventCode::EV_KEY(key), 0))
+        }
+        EventType::ButtonPress(button) => {
+            let button = rdev_button_to_evdev_key(button)?;
+            Some(InputEvent::new(time, &EventCode::EV_KEY(button), 1))
+        }
+        EventType::ButtonRelease(button) => {
+            let button = rdev_button_to_evdev_key(button)?;
+            Some(InputEvent::new(time, &EventCode::EV_KEY(button), 0))
+        }
+        EventType::MouseMove { .. } => None,
+        EventType::Wheel { delta_x, delta_y } => {
+            //TODO allow both x and y movements simultaneously
+            if *delta_x != 0 {
+                let delta_x = (*delta_x).try_into().ok()?;
+                Some(InputEvent::new(
+                    time,
+                    &EventCode::EV_REL(EV_REL::REL_HWHEEL),
+                    delta_x,
+                ))
+            } else {
+                let delta_y = (*delta_y).try_into().ok()?;
+                Some(InputEvent::new(
+                    time,
+                    &EventCode::EV_REL(EV_REL::REL_WHEEL),
+                    delta_y,
+                ))
+            }
+        }
+    }
+}
+
+/// Relative evdev pointers move by the difference with `from`, where the
+/// pointer was, absolute ones go to `to` on their axes spanning `size`.
+fn rdev_move_to_evdev_events(
+    from: (f64, f64),
+    to: (f64, f64),
+    size: (f64, f64),
+    abs: Option<&AbsAxes>,
+    time: &TimeVal,
+) -> Vec<InputEvent> {
+    if let Some(abs) = abs {
+        return vec![
+            InputEvent::new(
+                time,
+                &EventCode::EV_ABS(EV_ABS::ABS_X),
+                screen_to_abs(to.0, abs.x, size.0),
+            ),
+            InputEvent::new(
+                time,
+                &EventCode::EV_ABS(EV_ABS::ABS_Y),
+                screen_to_abs(to.1, abs.y, size.1),
+            ),
+        ];
+    }
+    let mut events = vec![];
+    let (dx, dy) = ((to.0 - from.0).round(), (to.1 - from.1).round());
+    if dx != 0.0 {
+        events.push(InputEvent::new(
+            time,
+            &EventCode::EV_REL(EV_REL::REL_X),
+            dx as i32,
+        ));
+    }
+    if dy != 0.0 {
+        events.push(InputEvent::new(
+            time,
+            &EventCode::EV_REL(EV_REL::REL_Y),
+            dy as i32,
+        ));
+    }
+    events
+}
+
+/// Converts evdev events to rdev ones and back, keeping track of the keyboard
+/// state and of the pointer position.
+///
+/// Relative moves can only be guessed: the X server applies its acceleration
+/// once it receives them, and other software moves the pointer too. The
+/// position gets read back from the X server at the start of each frame, so
+/// that guesses only ever go as far as one frame.
+///
+/// Listening works without an X server too, the events then have no name and
+/// the pointer position is only a guess from where it started.
+struct Converter {
+    keyboard: Option<Keyboard>,
+    modifiers: Modifiers,
+    display: Option<Display>,
+    /// Whether the last event ended a frame.
+    frame_done: bool,
+    x: f64,
+    y: f64,
+    w: f64,
+    h: f64,
+    /// Pointer position before the last converted event.
+    previous: (f64, f64),
+}
 
-pub fn grab<T>(callback: T) -> Result<(), GrabError>
-where
-    T: Fn(Event) -> Option<Event> + 'static,
-{
-    let mut kb = Keyboard::new().ok_or(GrabError::KeyboardError)?;
-    let display = Display::new().ok_or(GrabError::MissingDisplayError)?;
-    let (width, height) = display.get_size().ok_or(GrabError::MissingDisplayError)?;
-    let (current_x, current_y) = display
-        .get_mouse_pos()
-        .ok_or(GrabError::MissingDisplayError)?;
-    let mut x = current_x as f64;
-    let mut y = current_y as f64;
-    let w = width as f64;
-    let h = height as f64;
-    filter_map_events(|event| {
-        let event_type = match evdev_event_to_rdev_event(&event, &mut x, &mut y, w, h) {
-            Some(rdev_event) => rdev_event,
-            // If we can't convert event, simulate it
-            None => return (Some(event), GrabStatus::Continue),
+impl Converter {
+    fn new() -> Result<Converter, GrabError> {
+        let keyboard = Keyboard::new().ok_or(GrabError::KeyboardError)?;
+        let display = Display::new().ok_or(GrabError::MissingDisplayError)?;
+        let (width, height) = display.get_size().ok_or(GrabError::MissingDisplayError)?;
+        let (current_x, current_y) = display
+            .get_mouse_pos()
+            .ok_or(GrabError::MissingDisplayError)?;
+        let modifiers = display
+            .get_modifiers()
+            .ok_or(GrabError::MissingDisplayError)?;
+        Ok(Converter {
+            keyboard: Some(keyboard),
+            modifiers,
+            display: Some(display),
+            frame_done: false,
+            x: current_x as f64,
+            y: current_y as f64,
+            w: width as f64,
+            h: height as f64,
+            previous: (current_x as f64, current_y as f64),
+        })
+    }
+
+    /// For `listen`, which can do without an X server. Without one, the
+    /// pointer starts in the top left corner of the framebuffer, and absolute
+    /// devices report their own coordinates when there's no framebuffer
+    /// either.
+    fn passive() -> Converter {
+        let display = Display::new();
+        let (w, h) = display
+            .as_ref()
+            .and_then(Display::get_size)
+            .map(|(width, height)| (width as f64, height as f64))
+            .or_else(framebuffer_size)
+            .unwrap_or((f64::INFINITY, f64::INFINITY));
+        let (x, y) = display
+            .as_ref()
+            .and_then(Display::get_mouse_pos)
+            .map_or((0.0, 0.0), |(x, y)| (x as f64, y as f64));
+        let modifiers = display
+            .as_ref()
+            .and_then(Display::get_modifiers)
+            .unwrap_or_else(Modifiers::empty);
+        Converter {
+            keyboard: Keyboard::new(),
+            modifiers,
+            display,
+            frame_done: false,
+            x,
+            y,
+            w,
+            h,
+            previous: (x, y),
+        }
+    }
+
+    fn rdev_event(&mut self, event: &InputEvent, device: &GrabbedDevice) -> Option<Event> {
+        if self.frame_done {
+            if let Some((x, y)) = self.display.as_ref().and_then(Display::get_mouse_pos) {
+                self.x = x as f64;
+                self.y = y as f64;
+            }
+        }
+        self.frame_done = event.event_code == EventCode::EV_SYN(EV_SYN::SYN_REPORT);
+        self.previous = (self.x, self.y);
+        let abs = device.abs.as_ref();
+        // Without a screen size, absolute devices map onto their own range.
+        let (w, h) = match abs {
+            Some(abs) if self.w.is_infinite() => {
+                ((abs.x.1 - abs.x.0) as f64, (abs.y.1 - abs.y.0) as f64)
+            }
+            _ => (self.w, self.h),
         };
-        let name = kb.add(&event_type);
-        let rdev_event = Event {
+        let event_type = evdev_event_to_rdev_event(event, &mut self.x, &mut self.y, w, h, abs)?;
+        let name = self
+            .keyboard
+            .as_mut()
+            .and_then(|keyboard| keyboard.add(&event_type));
+        self.modifiers.update(&event_type);
+        Some(Event {
             time: SystemTime::now(),
             name,
             event_type,
+            modifiers: self.modifiers,
+            device: Some(device.info.clone()),
+        })
+    }
+
+    /// The last converted event doesn't get sent, so the pointer doesn't move.
+    fn drop_last(&mut self) {
+        self.x = self.previous.0;
+        self.y = self.previous.1;
+    }
+
+    /// Converts `event` sent in place of an evdev event, None if evdev can't
+    /// represent it.
+    fn evdev_events(
+        &mut self,
+        event: &EventType,
+        device: &GrabbedDevice,
+        time: &TimeVal,
+    ) -> Option<Vec<InputEvent>> {
+        match event {
+            EventType::MouseMove { x, y } => {
+                let from = (self.x, self.y);
+                self.x = *x;
+                self.y = *y;
+                let size = (self.w, self.h);
+                let abs = device.abs.as_ref();
+                Some(rdev_move_to_evdev_events(from, (*x, *y), size, abs, time))
+            }
+            event => rdev_event_to_evdev_event(event, time).map(|event| vec![event]),
+        }
+    }
+
+    /// Each event gets its own SYN_REPORT, so that a press and release of the
+    /// same key don't end up in the same frame. Events evdev can't represent
+    /// are skipped.
+    fn evdev_frames(
+        &mut self,
+        events: &[EventType],
+        device: &GrabbedDevice,
+        time: &TimeVal,
+    ) -> Vec<InputEvent> {
+        let mut frames = vec![];
+        for event in events {
+            if let Some(events) = self.evdev_events(event, device, time) {
+                frames.extend(events);
+                frames.push(InputEvent::new(
+                    time,
+                    &EventCode::EV_SYN(EV_SYN::SYN_REPORT),
+                    0,
+                ));
+            }
+        }
+        frames
+    }
+}
+
+/// evdev sends held keys again with a value of 2, and drops presses of keys
+/// that are down already: sending a repeated key as a press would stop it from
+/// repeating.
+fn keep_repeat(event: &InputEvent, events: &mut [InputEvent]) {
+    if event.value != 2 {
+        return;
+    }
+    for sent in events {
+        if let (EventCode::EV_KEY(_), 1) = (&sent.event_code, sent.value) {
+            sent.value = 2;
+        }
+    }
+}
+
+/// Turns a `grab` callback into one for `GrabbedDevices::run`.
+fn grab_filter<T>(
+    mut converter: Converter,
+    mut callback: T,
+) -> impl FnMut(InputEvent, &GrabbedDevice) -> (Vec<InputEvent>, GrabStatus)
+where
+    T: FnMut(Event) -> Option<Event>,
+{
+    move |event, device| {
+        let rdev_event = match converter.rdev_event(&event, device) {
+            Some(rdev_event) => rdev_event,
+            // If we can't convert event, simulate it
+            None => return (vec![event], GrabStatus::Continue),
         };
-        if callback(rdev_event).is_some() {
-            (Some(event), GrabStatus::Continue)
-        } else {
+        let event_type = rdev_event.event_type;
+        let events = match callback(rdev_event) {
+            Some(returned) if returned.event_type == event_type => vec![event],
             // callback returns None, swallow the event
-            (None, GrabStatus::Continue)
-        }
-    })?;
+            None => {
+                converter.drop_last();
+                vec![]
+            }
+            Some(returned) => {
+                let sent = (converter.x, converter.y);
+                converter.drop_last();
+                // If the returned event can't be sent, send the original one
+                match converter.evdev_events(&returned.event_type, device, &event.time) {
+                    Some(mut events) => {
+                        keep_repeat(&event, &mut events);
+                        events
+                    }
+                    None => {
+                        converter.x = sent.0;
+                        converter.y = sent.1;
+                        vec![event]
+                    }
+                }
+            }
+        };
+        (events, GrabStatus::Continue)
+    }
+}
+
+pub fn grab<T>(callback: T) -> Result<(), GrabError>
+where
+    T: FnMut(Event) -> Option<Event>,
+{
+    grab_config(&GrabConfig::default(), callback)
+}
+
+/// Same as `grab`, through `backend`. Only evdev can grab for now.
+pub fn grab_with<T>(backend: Backend, callback: T) -> Result<(), GrabError>
+where
+    T: FnMut(Event) -> Option<Event>,
+{
+    match backend {
+        Backend::Evdev => grab(callback),
+        _ => Err(GrabError::UnsupportedBackend),
+    }
+}
+
+pub fn grab_config<T>(config: &GrabConfig, callback: T) -> Result<(), GrabError>
+where
+    T: FnMut(Event) -> Option<Event>,
+{
+    let converter = Converter::new()?;
+    grab_devices(config, None)?.run(grab_filter(converter, callback))?;
     Ok(())
 }
 
-pub fn filter_map_events<F>(mut func: F) -> io::Result<()>
+/// Grabbing global events on a thread of its own, returning as soon as the
+/// devices are grabbed with a `GrabHandle` to stop it.
+///
+/// ```no_run
+/// use rdev::{grab_with_handle, EventType, Key};
+///
+/// let handle = grab_with_handle(|event| match event.event_type {
+///     EventType::KeyPress(Key::CapsLock) | EventType::KeyRelease(Key::CapsLock) => None,
+///     _ => Some(event),
+/// })
+/// .expect("Could not grab");
+/// std::thread::sleep(std::time::Duration::from_secs(10));
+/// // The keyboard works as usual again.
+/// handle.stop().expect("Could not stop grabbing");
+/// ```
+pub fn grab_with_handle<T>(callback: T) -> Result<GrabHandle, GrabError>
+where
+    T: FnMut(Event) -> Option<Event> + Send + 'static,
+{
+    grab_with_handle_config(GrabConfig::default(), callback)
+}
+
+pub fn grab_with_handle_config<T>(config: GrabConfig, callback: T) -> Result<GrabHandle, GrabError>
 where
-    F: FnMut(InputEvent) -> (Option<InputEvent>, GrabStatus),
+    T: FnMut(Event) -> Option<Event> + Send + 'static,
 {
-    let (epoll_fd, mut devices, output_devices) = setup_devices()?;
-    let mut inotify = setup_inotify(epoll_fd, &devices)?;
-
-    //grab devices
-    devices
-        .iter_mut()
-        .try_for_each(|device| device.grab(evdev_rs::GrabMode::Grab))?;
-
-    // create buffer for epoll to fill
-    let mut epoll_buffer = [epoll::Event::new(epoll::Events::empty(), 0); 4];
-    let mut inotify_buffer = vec![0_u8; 4096];
-    'event_loop: loop {
-        let num_events = epoll::wait(epoll_fd, -1, &mut epoll_buffer)?;
-
-        //map and simulate events, dealing with
-        'events: for event in &epoll_buffer[0..num_events] {
-            // new device file created
-            if event.data == INOTIFY_DATA {
-                for event in inotify.read_events(&mut inotify_buffer)? {
-                    assert!(
-                        event.mask.contains(inotify::EventMask::CREATE),
-                        "inotify is listening for events other than file creation"
-                    );
-                    add_device_to_epoll_from_inotify_event(epoll_fd, event, &mut devices)?;
+    let stop = Arc::new(EventFd::new()?);
+    let thread_stop = stop.clone();
+    let (ready_send, ready_recv) = sync_channel(1);
+    let thread = thread::spawn(move || {
+        let setup = Converter::new().and_then(|converter| {
+            let devices = grab_devices(&config, Some(&thread_stop))?;
+            Ok((converter, devices))
+        });
+        let (converter, mut devices) = match setup {
+            Ok(setup) => setup,
+            Err(error) => {
+                ready_send.send(Err(error)).ok();
+                return Ok(());
+            }
+        };
+        ready_send.send(Ok(())).ok();
+        devices.run(grab_filter(converter, callback))?;
+        Ok(())
+    });
+    match ready_recv.recv() {
+        Ok(Ok(())) => Ok(GrabHandle { stop, thread }),
+        Ok(Err(error)) => Err(error),
+        // The thread panicked before getting ready.
+        Err(_) => panic::resume_unwind(thread.join().unwrap_err()),
+    }
+}
+
+/// Handle on a grab started with `grab_with_handle`.
+pub struct GrabHandle {
+    stop: Arc<EventFd>,
+    thread: JoinHandle<Result<(), GrabError>>,
+}
+
+impl GrabHandle {
+    /// Stops grabbing and waits for the grab thread to return. Every device
+    /// gets ungrabbed and its uinput clone destroyed, even if the callback
+    /// panicked.
+    pub fn stop(self) -> Result<(), GrabError> {
+        // Fails only if the thread is gone already, which join reports.
+        self.stop.notify().ok();
+        match self.thread.join() {
+            Ok(result) => result,
+            Err(error) => panic::resume_unwind(error),
+        }
+    }
+}
+
+/// Grabbing global events, sending the events returned by `callback` in place
+/// of each one: none to swallow it, or several to expand it into a macro.
+/// Returning the event's own type passes it through untouched. The events are
+/// sent through the device the grabbed event came from, each in its own
+/// SYN_REPORT frame. Presses sent in place of a key repeat are repeats too, so
+/// that remapped keys keep repeating.
+///
+/// ```no_run
+/// use rdev::{grab_emit, EventType, Key};
+///
+/// // This will block.
+/// grab_emit(|event| match event.event_type {
+///     // Types "hi"
+///     EventType::KeyPress(Key::F5) => vec![
+///         EventType::KeyPress(Key::KeyH),
+///         EventType::KeyRelease(Key::KeyH),
+///         EventType::KeyPress(Key::KeyI),
+///         EventType::KeyRelease(Key::KeyI),
+///     ],
+///     EventType::KeyRelease(Key::F5) => vec![],
+///     event_type => vec![event_type],
+/// })
+/// .expect("Could not grab");
+/// ```
+pub fn grab_emit<T>(callback: T) -> Result<(), GrabError>
+where
+    T: FnMut(Event) -> Vec<EventType>,
+{
+    grab_emit_config(&GrabConfig::default(), callback)
+}
+
+pub fn grab_emit_config<T>(config: &GrabConfig, mut callback: T) -> Result<(), GrabError>
+where
+    T: FnMut(Event) -> Vec<EventType>,
+{
+    let mut converter = Converter::new()?;
+    grab_devices(config, None)?.run(|event, device| {
+        let rdev_event = match converter.rdev_event(&event, device) {
+            Some(rdev_event) => rdev_event,
+            None => return (vec![event], GrabStatus::Continue),
+        };
+        let event_type = rdev_event.event_type;
+        let events = callback(rdev_event);
+        if events == [event_type] {
+            return (vec![event], GrabStatus::Continue);
+        }
+        converter.drop_last();
+        let mut frames = converter.evdev_frames(&events, device, &event.time);
+        keep_repeat(&event, &mut frames);
+        (frames, GrabStatus::Continue)
+    })?;
+    Ok(())
+}
+
+pub fn remap(mut remapper: Remapper) -> Result<(), GrabError> {
+    grab_emit(move |event| remapper.process(&event.event_type))
+}
+
+/// Listens to the input devices without grabbing them, for `listen` in
+/// Wayland and in the virtual console where there's no X server to ask.
+pub struct EvdevListener {
+    converter: Converter,
+    devices: GrabbedDevices,
+}
+
+impl EvdevListener {
+    /// Opens every input device, notifying `stop` ends `run`.
+    pub fn new(stop: &EventFd) -> Result<EvdevListener, ListenError> {
+        let devices = GrabbedDevices::new(&GrabConfig::default(), Some(stop), false)?;
+        Ok(EvdevListener {
+            converter: Converter::passive(),
+            devices,
+        })
+    }
+
+    /// Sends events until `stop` gets notified.
+    pub fn run<T>(&mut self, mut send: T) -> Result<(), ListenError>
+    where
+        T: FnMut(Event),
+    {
+        let converter = &mut self.converter;
+        self.devices.run(|event, device| {
+            if let Some(event) = converter.rdev_event(&event, device) {
+                send(event);
+            }
+            (vec![], GrabStatus::Continue)
+        })?;
+        Ok(())
+    }
+}
+
+/// A grabbed device, along with the uinput clone sending the events in its
+/// place. Devices only listened to have no clone.
+struct GrabbedDevice {
+    device: Device,
+    output: Option<UInputDevice>,
+    info: DeviceInfo,
+    abs: Option<AbsAxes>,
+}
+
+impl GrabbedDevice {
+    fn new(mut device: Device, info: DeviceInfo, grab: bool) -> io::Result<GrabbedDevice> {
+        let output = if grab {
+            device.grab(evdev_rs::GrabMode::Grab)?;
+            Some(UInputDevice::create_from_device(&device)?)
+        } else {
+            None
+        };
+        let abs = AbsAxes::new(&device);
+        Ok(GrabbedDevice {
+            device,
+            output,
+            info,
+            abs,
+        })
+    }
+
+    fn raw_fd(&self) -> RawFd {
+        self.device.fd().unwrap().into_raw_fd()
+    }
+}
+
+/// The grabbed devices, at the index their epoll data points to. Dropping it
+/// ungrabs the devices and destroys their clones, so that they don't stay
+/// grabbed when the callback panics.
+///
+/// `listen` opens them without grabbing, the events then reach everyone as
+/// usual and whatever `run` returns for them is ignored.
+struct GrabbedDevices {
+    epoll_fd: RawFd,
+    /// Unplugged devices leave their slot empty for the next one plugged in.
+    devices: Vec<Option<GrabbedDevice>>,
+    inotify: Inotify,
+    /// Selects the devices plugged in while grabbing.
+    config: GrabConfig,
+    grab: bool,
+}
+
+/// Grabs the devices `config` selects. Those plugged in later get grabbed
+/// too, but there has to be one to start with.
+fn grab_devices(config: &GrabConfig, stop: Option<&EventFd>) -> Result<GrabbedDevices, GrabError> {
+    let devices = GrabbedDevices::new(config, stop, true)?;
+    if devices.devices.is_empty() {
+        return Err(GrabError::NoDeviceError);
+    }
+    Ok(devices)
+}
+
+impl GrabbedDevices {
+    /// Grabs the devices selected by `config`, or only opens them unless
+    /// `grab`. Notifying `stop` ends `run`.
+    fn new(config: &GrabConfig, stop: Option<&EventFd>, grab: bool) -> io::Result<GrabbedDevices> {
+        // Set up inotify to listen for devices being plugged in, before
+        // listing them so that none gets missed.
+        let inotify = inotify_devices()?;
+        let mut grabbed = GrabbedDevices {
+            epoll_fd: epoll::create(true)?,
+            devices: vec![],
+            inotify,
+            config: config.clone(),
+            grab,
+        };
+        let epoll_event = epoll::Event::new(EPOLLIN, INOTIFY_DATA);
+        let inotify_fd = grabbed.inotify.as_raw_fd();
+        epoll::ctl(grabbed.epoll_fd, EPOLL_CTL_ADD, inotify_fd, epoll_event)?;
+        if let Some(stop) = stop {
+            let epoll_event = epoll::Event::new(EPOLLIN, STOP_DATA);
+            epoll::ctl(
+                grabbed.epoll_fd,
+                EPOLL_CTL_ADD,
+                stop.as_raw_fd(),
+                epoll_event,
+            )?;
+        }
+        // A device that can't be opened, like a YubiKey only root can read,
+        // doesn't keep the others from being grabbed.
+        let mut error = None;
+        for path in get_device_paths(DEV_PATH)? {
+            if let Err(add_error) = grabbed.add(&path) {
+                error.get_or_insert(add_error);
+            }
+        }
+        match error {
+            Some(error) if grabbed.devices.is_empty() => Err(error),
+            _ => Ok(grabbed),
+        }
+    }
+
+    /// Grabs the device at `path` if `config` selects it, and if it isn't
+    /// grabbed already.
+    fn add(&mut self, path: &Path) -> io::Result<()> {
+        let known = self
+            .devices
+            .iter()
+            .flatten()
+            .any(|grabbed| grabbed.info.path.as_deref() == Some(path));
+        if known {
+            return Ok(());
+        }
+        // Devices `config` leaves out don't even get opened.
+        if let Some(info) = sysfs_device_info(path) {
+            if !self.config.matches(&info) {
+                return Ok(());
+            }
+        }
+        let (device, info) = match open_device(path, &self.config)? {
+            Some(opened) => opened,
+            None => return Ok(()),
+        };
+        // Clones of devices grabbed elsewhere are the only way to hear from
+        // them when listening.
+        if self.grab && self.is_clone(&info) {
+            return Ok(());
+        }
+        let grabbed = GrabbedDevice::new(device, info, self.grab)?;
+        let slot = match self.devices.iter().position(Option::is_none) {
+            Some(slot) => slot,
+            None => {
+                self.devices.push(None);
+                self.devices.len() - 1
+            }
+        };
+        let epoll_event = epoll::Event::new(EPOLLIN, slot as u64);
+        epoll::ctl(self.epoll_fd, EPOLL_CTL_ADD, grabbed.raw_fd(), epoll_event)?;
+        self.devices[slot] = Some(grabbed);
+        Ok(())
+    }
+
+    /// Whether `info` describes the uinput clone of a grabbed device, which
+    /// looks like the device it clones but is virtual.
+    fn is_clone(&self, info: &DeviceInfo) -> bool {
+        let is_virtual = info
+            .path
+            .as_ref()
+            .and_then(|path| path.file_name())
+            .and_then(|name| read_link(Path::new(SYS_PATH).join(name)).ok())
+            .map(|link| link.to_string_lossy().contains("/virtual/"))
+            .unwrap_or(false);
+        is_virtual
+            && self.devices.iter().flatten().any(|grabbed| {
+                grabbed.info.name == info.name
+                    && grabbed.info.vendor == info.vendor
+                    && grabbed.info.product == info.product
+            })
+    }
+
+    /// Drops the device in `slot` along with its clone.
+    fn remove(&mut self, slot: usize) {
+        if let Some(grabbed) = self.devices.get_mut(slot).and_then(Option::take) {
+            let empty_event = epoll::Event::new(epoll::Events::empty(), 0);
+            // Closing the device removes it from epoll anyway.
+            epoll::ctl(self.epoll_fd, EPOLL_CTL_DEL, grabbed.raw_fd(), empty_event).ok();
+        }
+    }
+
+    fn read_inotify(&mut self, buffer: &mut [u8]) -> io::Result<()> {
+        for event in self.inotify.read_events(buffer)? {
+            let path = match event.name {
+                Some(name) => Path::new(DEV_PATH).join(name),
+                None => continue,
+            };
+            if event.mask.contains(EventMask::DELETE) {
+                let slot = self.devices.iter().position(|grabbed| {
+                    grabbed
+                        .as_ref()
+                        .and_then(|grabbed| grabbed.info.path.as_deref())
+                        == Some(path.as_path())
+                });
+                if let Some(slot) = slot {
+                    self.remove(slot);
                 }
-            } else {
-                // Input device recieved event
-                let device_idx = event.data as usize;
-                let device = devices.get(device_idx).unwrap();
-                while device.has_event_pending() {
-                    //TODO: deal with EV_SYN::SYN_DROPPED
-                    let (_, event) = match device.next_event(evdev_rs::ReadFlag::NORMAL) {
-                        Ok(event) => event,
-                        Err(_) => {
-                            let device_fd = device.fd().unwrap().into_raw_fd();
-                            let empty_event = epoll::Event::new(epoll::Events::empty(), 0);
-                            epoll::ctl(epoll_fd, EPOLL_CTL_DEL, device_fd, empty_event)?;
-                            continue 'events;
-                        }
-                    };
-                    let (event, grab_status) = func(event);
+            } else if is_device_file(&path) {
+                // A new device file may not be readable yet, it will be once
+                // udev sets its permissions, which ATTRIB reports.
+                self.add(&path).ok();
+            }
+        }
+        Ok(())
+    }
 
-                    if let (Some(event), Some(out_device)) = (event, output_devices.get(device_idx))
-                    {
-                        out_device.write_event(&event)?;
-                    }
-                    if grab_status == GrabStatus::Stop {
-                        break 'event_loop;
+    /// Reads the pending events of the device in `slot`.
+    fn read<F>(&mut self, slot: usize, func: &mut F) -> io::Result<GrabStatus>
+    where
+        F: FnMut(InputEvent, &GrabbedDevice) -> (Vec<InputEvent>, GrabStatus),
+    {
+        let grabbed = match self.devices.get(slot) {
+            Some(Some(grabbed)) => grabbed,
+            // Removed since epoll reported it
+            _ => return Ok(GrabStatus::Continue),
+        };
+        let mut flag = ReadFlag::NORMAL;
+        while flag == ReadFlag::SYNC || grabbed.device.has_event_pending() {
+            let event = match grabbed.device.next_event(flag) {
+                Ok((ReadStatus::Success, event)) => event,
+                // EV_SYN::SYN_DROPPED, the kernel buffer overflowed and events
+                // got lost. libevdev then hands out the difference with the
+                // state before as sync events, so that no key stays pressed.
+                Ok((ReadStatus::Sync, _)) if flag == ReadFlag::NORMAL => {
+                    flag = ReadFlag::SYNC;
+                    continue;
+                }
+                Ok((ReadStatus::Sync, event)) => event,
+                Err(error) if error.kind() == io::ErrorKind::WouldBlock => {
+                    if flag == ReadFlag::NORMAL {
+                        break;
                     }
+                    // Done syncing
+                    flag = ReadFlag::NORMAL;
+                    continue;
+                }
+                // The device got unplugged
+                Err(_) => {
+                    self.remove(slot);
+                    return Ok(GrabStatus::Continue);
+                }
+            };
+            let (events, grab_status) = func(event, grabbed);
+
+            if let Some(output) = &grabbed.output {
+                for event in &events {
+                    output.write_event(event)?;
                 }
             }
+            if grab_status == GrabStatus::Stop {
+                return Ok(GrabStatus::Stop);
+            }
         }
+        Ok(GrabStatus::Continue)
     }
 
-    for device in devices.iter_mut() {
-        //ungrab devices, ignore errors
-        device.grab(evdev_rs::GrabMode::Ungrab).ok();
+    fn run<F>(&mut self, mut func: F) -> io::Result<()>
+    where
+        F: FnMut(InputEvent, &GrabbedDevice) -> (Vec<InputEvent>, GrabStatus),
+    {
+        // create buffer for epoll to fill
+        let mut epoll_buffer = [epoll::Event::new(epoll::Events::empty(), 0); 4];
+        let mut inotify_buffer = vec![0_u8; 4096];
+        loop {
+            let num_events = epoll::wait(self.epoll_fd, -1, &mut epoll_buffer)?;
+            for event in &epoll_buffer[0..num_events] {
+                match event.data {
+                    STOP_DATA => return Ok(()),
+                    // device files created, deleted or made readable
+                    INOTIFY_DATA => self.read_inotify(&mut inotify_buffer)?,
+                    // Input device recieved event
+                    slot => {
+                        if self.read(slot as usize, &mut func)? == GrabStatus::Stop {
+                            return Ok(());
+                        }
+                    }
+                }
+            }
+        }
     }
+}
 
-    epoll::close(epoll_fd)?;
-    Ok(())
+impl Drop for GrabbedDevices {
+    fn drop(&mut self) {
+        for grabbed in self.devices.iter_mut().flatten() {
+            //ungrab devices, ignore errors
+            if grabbed.output.is_some() {
+                grabbed.device.grab(evdev_rs::GrabMode::Ungrab).ok();
+            }
+        }
+        epoll::close(self.epoll_fd).ok();
+    }
 }
 
-static DEV_PATH: &str = "/dev/input";
-const INOTIFY_DATA: u64 = u64::max_value();
+pub static DEV_PATH: &str = "/dev/input";
+static SYS_PATH: &str = "/sys/class/input";
+static FB_SIZE_PATH: &str = "/sys/class/graphics/fb0/virtual_size";
+const INOTIFY_DATA: u64 = u64::MAX;
+const STOP_DATA: u64 = u64::MAX - 1;
 const EPOLLIN: epoll::Events = epoll::Events::EPOLLIN;
 
 /// Whether to continue grabbing events or to stop
-/// Used in `filter_map_events` (and others)
+/// Used in `GrabbedDevices::run` (and others)
 #[derive(Debug, Eq, PartialEq, Hash)]
 pub enum GrabStatus {
     /// Stop grabbing
@@ -418,7 +1116,7 @@ pub enum GrabStatus {
     Stop,
 }
 
-fn get_device_files<T>(path: T) -> io::Result<Vec<File>>
+pub fn get_device_paths<T>(path: T) -> io::Result<Vec<PathBuf>>
 where
     T: AsRef<Path>,
 {
@@ -431,99 +1129,161 @@ where
         }
 
         let path = entry.path();
-        let file_name_bytes = match path.file_name() {
-            Some(file_name) => file_name.as_bytes(),
-            None => continue, // file_name was "..", should be impossible
-        };
-        // skip filenames matching "mouse.* or mice".
-        // these files don't play nice with libevdev, not sure why
-        // see: https://askubuntu.com/questions/1043832/difference-between-dev-input-mouse0-and-dev-input-mice
-        if file_name_bytes == OsStr::new("mice").as_bytes()
-            || file_name_bytes
-                .get(0..=1)
-                .map(|s| s == OsStr::new("js").as_bytes())
-                .unwrap_or(false)
-            || file_name_bytes
-                .get(0..=4)
-                .map(|s| s == OsStr::new("mouse").as_bytes())
-                .unwrap_or(false)
-        {
-            continue;
+        if is_device_file(&path) {
+            res.push(path);
         }
-        res.push(File::open(path)?);
     }
     Ok(res)
 }
 
-fn epoll_watch_all<'a, T>(device_files: T) -> io::Result<RawFd>
-where
-    T: Iterator<Item = &'a File>,
-{
-    let epoll_fd = epoll::create(true)?;
-    // add file descriptors to epoll
-    for (file_idx, file) in device_files.enumerate() {
-        let epoll_event = epoll::Event::new(EPOLLIN, file_idx as u64);
-        epoll::ctl(epoll_fd, EPOLL_CTL_ADD, file.as_raw_fd(), epoll_event)?;
+/// Whether `path` is a device file `grab` can handle.
+fn is_device_file(path: &Path) -> bool {
+    let file_name_bytes = match path.file_name() {
+        Some(file_name) => file_name.as_bytes(),
+        None => return false, // file_name was "..", should be impossible
+    };
+    // skip filenames matching "mouse.* or mice".
+    // these files don't play nice with libevdev, not sure why
+    // see: https://askubuntu.com/questions/1043832/difference-between-dev-input-mouse0-and-dev-input-mice
+    !(file_name_bytes == OsStr::new("mice").as_bytes()
+        || file_name_bytes
+            .get(0..=1)
+            .map(|s| s == OsStr::new("js").as_bytes())
+            .unwrap_or(false)
+        || file_name_bytes
+            .get(0..=4)
+            .map(|s| s == OsStr::new("mouse").as_bytes())
+            .unwrap_or(false))
+}
+
+/// N in /dev/input/eventN.
+fn device_id(path: &Path) -> u32 {
+    path.file_name()
+        .and_then(|name| name.to_str()?.strip_prefix("event")?.parse().ok())
+        .unwrap_or_default()
+}
+
+/// Describes `device`, opened from `path`.
+fn device_info(path: &Path, device: &Device) -> DeviceInfo {
+    DeviceInfo {
+        id: device_id(path),
+        path: Some(path.to_path_buf()),
+        name: device.name().unwrap_or_default().to_string(),
+        phys: device.phys().map(str::to_string),
+        vendor: device.vendor_id(),
+        product: device.product_id(),
+        keyboard: device.has_event_code(&EventCode::EV_KEY(EV_KEY::KEY_A)),
+        pointer: device.has_event_code(&EventCode::EV_REL(EV_REL::REL_X))
+            || device.has_property(&InputProp::INPUT_PROP_POINTER)
+            || AbsAxes::new(device).is_some(),
     }
-    Ok(epoll_fd)
+}
+
+/// Describes the device at `path` the same way as `device_info` does, from
+/// sysfs which anyone can read.
+fn sysfs_device_info(path: &Path) -> Option<DeviceInfo> {
+    let sys_path = Path::new(SYS_PATH).join(path.file_name()?).join("device");
+    let read = |file: &str| {
+        read_to_string(sys_path.join(file))
+            .ok()
+            .map(|content| content.trim().to_string())
+    };
+    let bits = |file: &str| read(file).map(|bitmap| parse_bitmap(&bitmap));
+    let id = |file: &str| u16::from_str_radix(&read(file)?, 16).ok();
+    let keys = bits("capabilities/key")?;
+    let abs = bits("capabilities/abs")?;
+    let properties = bits("properties")?;
+    // Same as `AbsAxes::new`
+    let pointer_property = has_bit(&properties, InputProp::INPUT_PROP_POINTER as usize);
+    let absolute = !pointer_property
+        && (has_bit(&keys, EV_KEY::BTN_LEFT as usize)
+            || has_bit(&keys, EV_KEY::BTN_TOUCH as usize))
+        && has_bit(&abs, EV_ABS::ABS_X as usize)
+        && has_bit(&abs, EV_ABS::ABS_Y as usize);
+    Some(DeviceInfo {
+        id: device_id(path),
+        path: Some(path.to_path_buf()),
+        name: read("name")?,
+        phys: read("phys").filter(|phys| !phys.is_empty()),
+        vendor: id("id/vendor")?,
+        product: id("id/product")?,
+        keyboard: has_bit(&keys, EV_KEY::KEY_A as usize),
+        pointer: has_bit(&bits("capabilities/rel")?, EV_REL::REL_X as usize)
+            || pointer_property
+            || absolute,
+    })
+}
+
+/// sysfs bitmaps are words of hex digits, the most significant first.
+fn parse_bitmap(bitmap: &str) -> Vec<c_ulong> {
+    bitmap
+        .split_whitespace()
+        .rev()
+        .map(|word| c_ulong::from_str_radix(word, 16).unwrap_or_default())
+        .collect()
+}
+
+fn has_bit(bitmap: &[c_ulong], bit: usize) -> bool {
+    let word_bits = c_ulong::BITS as usize;
+    matches!(bitmap.get(bit / word_bits), Some(word) if word >> (bit % word_bits) & 1 == 1)
+}
+
+/// Opens the device at `path`, None if `config` doesn't select it.
+fn open_device(path: &Path, config: &GrabConfig) -> io::Result<Option<(Device, DeviceInfo)>> {
+    let device = Device::new_from_fd(File::open(path)?)?;
+    let info = device_info(path, &device);
+    if config.matches(&info) {
+        Ok(Some((device, info)))
+    } else {
+        Ok(None)
+    }
+}
+
+/// Lists the devices `grab` can take over, to build a `GrabConfig`. Those
+/// the user can't open are listed too, from sysfs, unless sysfs lacks them.
+pub fn list_devices() -> Result<Vec<DeviceInfo>, GrabError> {
+    let mut devices = vec![];
+    for path in get_device_paths(DEV_PATH)? {
+        let info = match sysfs_device_info(&path) {
+            Some(info) => info,
+            None => match File::open(&path).and_then(Device::new_from_fd) {
+                Ok(device) => device_info(&path, &device),
+                Err(_) => continue,
+            },
+        };
+        devices.push(info);
+    }
+    Ok(devices)
+}
+
+/// Size of the console, as "width,height" in pixels.
+pub fn framebuffer_size() -> Option<(f64, f64)> {
+    let size = read_to_string(FB_SIZE_PATH).ok()?;
+    let (width, height) = size.trim().split_once(',')?;
+    Some((width.parse().ok()?, height.parse().ok()?))
 }
 
 fn inotify_devices() -> io::Result<Inotify> {
     let mut inotify = Inotify::init()?;
-    inotify.add_watch(DEV_PATH, WatchMask::CREATE)?;
+    inotify.add_watch(
+        DEV_PATH,
+        WatchMask::CREATE | WatchMask::DELETE | WatchMask::ATTRIB,
+    )?;
     Ok(inotify)
 }
 
-fn add_device_to_epoll_from_inotify_event(
-    epoll_fd: RawFd,
-    event: inotify::Event<&OsStr>,
-    devices: &mut Vec<Device>,
-) -> io::Result<()> {
-    let mut device_path = OsString::from(DEV_PATH);
-    device_path.push(OsString::from("/"));
-    device_path.push(event.name.unwrap());
-    // new plug events
-    let file = File::open(device_path)?;
-    let fd = file.as_raw_fd();
-    let device = Device::new_from_fd(file)?;
-    let event = epoll::Event::new(EPOLLIN, devices.len() as u64);
-    devices.push(device);
-    epoll::ctl(epoll_fd, EPOLL_CTL_ADD, fd, event)?;
-    Ok(())
-}
+#[cfg(test)]
+mod tests {
+    use super::*;
 
-/// Returns tuple of epoll_fd, all devices, and uinput devices, where
-/// uinputdevices is the same length as devices, and each uinput device is
-/// a libevdev copy of its corresponding device.The epoll_fd is level-triggered
-/// on any available data in the original devices.
-fn setup_devices() -> io::Result<(RawFd, Vec<Device>, Vec<UInputDevice>)> {
-    let device_files = get_device_files(DEV_PATH)?;
-    let epoll_fd = epoll_watch_all(device_files.iter())?;
-    let devices = device_files
-        .into_iter()
-        .map(Device::new_from_fd)
-        .collect::<io::Result<Vec<Device>>>()?;
-    let output_devices = devices
-        .iter()
-        .map(UInputDevice::create_from_device)
-        .collect::<io::Result<Vec<UInputDevice>>>()?;
-    Ok((epoll_fd, devices, output_devices))
-}
-
-/// Creates an inotify instance looking at /dev/input and adds it to an epoll instance.
-/// Ensures devices isnt too long, which would make the epoll data ambigious.
-fn setup_inotify(epoll_fd: RawFd, devices: &[Device]) -> io::Result<Inotify> {
-    //Ensure there is space for inotify at last epoll index.
-    if devices.len() as u64 >= INOTIFY_DATA {
-        eprintln!("number of devices: {}", devices.len());
-        return Err(io::Error::new(
-            io::ErrorKind::Other,
-            "too many device files!",
-        ));
+    #[test]
+    fn test_bitmap() {
+        // A keyboard's capabilities/key, KEY_ESC is bit 1 and KEY_A bit 30.
+        let bitmap = parse_bitmap("120013 0 0 0 0 0 0 0 0 0 0 0 0 ffffffffffffffff fffffffe");
+        assert!(!has_bit(&bitmap, 0));
+        assert!(has_bit(&bitmap, EV_KEY::KEY_ESC as usize));
+        assert!(has_bit(&bitmap, EV_KEY::KEY_A as usize));
+        assert!(!has_bit(&bitmap, EV_KEY::BTN_LEFT as usize));
+        assert!(!has_bit(&parse_bitmap("0"), EV_KEY::KEY_A as usize));
     }
-    // Set up inotify to listen for new devices being plugged in
-    let inotify = inotify_devices()?;
-    let epoll_event = epoll::Event::new(EPOLLIN, INOTIFY_DATA);
-    epoll::ctl(epoll_fd, EPOLL_CTL_ADD, inotify.as_raw_fd(), epoll_event)?;
-    Ok(inotify)
 }
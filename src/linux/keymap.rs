@@ -0,0 +1,128 @@
+use std::os::raw::{c_int, c_uint};
+use std::time::Duration;
+use x11::keysym;
+use x11::xlib::KeySym;
+
+pub const NO_SYMBOL: KeySym = 0;
+
+/// How long spare keycodes keep their temporary mapping once the X server got
+/// the last key typed with them. Clients look the key up when they get to its
+/// event, some only processing the MappingNotify at that point.
+pub const REMAP_DELAY: Duration = Duration::from_millis(100);
+
+/// The keysym typing `ch`, Latin-1 characters have their own keysyms,
+/// other ones use the Unicode range.
+/// https://www.cl.cam.ac.uk/~mgk25/ucs/keysyms.txt
+pub fn keysym_from_char(ch: char) -> KeySym {
+    match ch {
+        '\n' | '\r' => keysym::XK_Return.into(),
+        '\t' => keysym::XK_Tab.into(),
+        '\u{8}' => keysym::XK_BackSpace.into(),
+        ' '..='~' | '\u{a0}'..='\u{ff}' => ch as KeySym,
+        _ => 0x0100_0000 | ch as KeySym,
+    }
+}
+
+/// Copy of the core keyboard mapping: for every keycode, the keysyms it types
+/// at each level.
+pub struct Keymap {
+    min_keycode: c_int,
+    keysyms_per_keycode: usize,
+    keysyms: Vec<KeySym>,
+}
+
+impl Keymap {
+    /// `keysyms` lists `keysyms_per_keycode` keysyms for every keycode from
+    /// `min_keycode` on, as the X server sends them.
+    pub fn new(min_keycode: c_int, keysyms_per_keycode: usize, keysyms: Vec<KeySym>) -> Keymap {
+        Keymap {
+            min_keycode,
+            keysyms_per_keycode,
+            keysyms,
+        }
+    }
+
+    fn keycodes(&self) -> impl Iterator<Item = (c_uint, &[KeySym])> {
+        let min_keycode = self.min_keycode as c_uint;
+        self.keysyms
+            .chunks(self.keysyms_per_keycode)
+            .enumerate()
+            .map(move |(i, keysyms)| (min_keycode + i as c_uint, keysyms))
+    }
+
+    /// First keycode typing `keysym` at any level.
+    pub fn keycode(&self, keysym: KeySym) -> Option<c_uint> {
+        self.keycodes()
+            .find(|(_, keysyms)| keysyms.contains(&keysym))
+            .map(|(keycode, _)| keycode)
+    }
+
+    /// Keycode and level typing `keysym`. Level 1 needs shift, level 2 needs
+    /// AltGr (ISO_Level3_Shift), level 3 needs both.
+    pub fn find(&self, keysym: KeySym, with_level3: bool) -> Option<(c_uint, usize)> {
+        // The core mapping lists both levels of the first group, then of the
+        // second group, then levels 3 and 4 of the first group.
+        let levels: &[(usize, usize)] = if with_level3 {
+            &[(0, 0), (1, 1), (4, 2), (5, 3)]
+        } else {
+            &[(0, 0), (1, 1)]
+        };
+        levels.iter().find_map(|(index, level)| {
+            self.keycodes()
+                .find(|(_, keysyms)| keysyms.get(*index) == Some(&keysym))
+                .map(|(keycode, _)| (keycode, *level))
+        })
+    }
+
+    /// Keycodes that don't type anything.
+    pub fn spare_keycodes(&self) -> Vec<c_uint> {
+        self.keycodes()
+            .filter(|(_, keysyms)| keysyms.iter().all(|keysym| *keysym == NO_SYMBOL))
+            .map(|(keycode, _)| keycode)
+            .collect()
+    }
+
+    /// Maps `keysym` on every level of `keycode`, returns the keysyms to send
+    /// to the X server for it.
+    pub fn remap(&mut self, keycode: c_uint, keysym: KeySym) -> &[KeySym] {
+        let start = (keycode as usize - self.min_keycode as usize) * self.keysyms_per_keycode;
+        let keysyms = &mut self.keysyms[start..start + self.keysyms_per_keycode];
+        keysyms.iter_mut().for_each(|old| *old = keysym);
+        keysyms
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_keysym_from_char() {
+        assert_eq!(keysym_from_char('a'), keysym::XK_a.into());
+        assert_eq!(keysym_from_char('é'), keysym::XK_eacute.into());
+        assert_eq!(keysym_from_char('\n'), keysym::XK_Return.into());
+        assert_eq!(keysym_from_char('€'), 0x0100_20ac);
+    }
+
+    #[test]
+    fn test_keymap() {
+        let a = keysym::XK_a.into();
+        let shift_a = keysym::XK_A.into();
+        let ae = keysym::XK_ae.into();
+        #[rustfmt::skip]
+        let mut keymap = Keymap::new(8, 6, vec![
+            a, shift_a, a, shift_a, ae, keysym::XK_AE.into(),
+            0, 0, 0, 0, 0, 0,
+        ]);
+        assert_eq!(keymap.find(shift_a, false), Some((8, 1)));
+        assert_eq!(keymap.find(ae, false), None);
+        assert_eq!(keymap.find(ae, true), Some((8, 2)));
+        assert_eq!(keymap.keycode(ae), Some(8));
+        assert_eq!(keymap.spare_keycodes(), vec![9]);
+
+        let euro = keysym_from_char('€');
+        assert_eq!(keymap.remap(9, euro), &[euro; 6]);
+        assert_eq!(keymap.find(euro, false), Some((9, 0)));
+        assert!(keymap.spare_keycodes().is_empty());
+    }
+}
@@ -1,114 +1,297 @@
-extern crate libc;
-extern crate x11;
-use crate::linux::common::{convert, FALSE, KEYBOARD};
-use crate::linux::keyboard::Keyboard;
-use crate::rdev::{Event, ListenError};
-use std::convert::TryInto;
-use std::ffi::CStr;
-use std::os::raw::{c_char, c_int, c_uchar, c_uint, c_ulong};
-use std::ptr::null;
-use x11::xlib;
-use x11::xrecord;
-
-static mut RECORD_ALL_CLIENTS: c_ulong = xrecord::XRecordAllClients;
-static mut GLOBAL_CALLBACK: Option<Box<dyn FnMut(Event)>> = None;
+use crate::linux::backend::{RawListener, Recorder};
+#[cfg(feature = "unstable_grab")]
+use crate::linux::common::has_display;
+use crate::linux::common::EventFd;
+#[cfg(feature = "unstable_grab")]
+use crate::linux::grab::EvdevListener;
+use crate::rdev::{Backend, Event, ListenError};
+use lazy_static::lazy_static;
+use std::collections::HashMap;
+use std::panic;
+use std::sync::mpsc::{channel, sync_channel, Receiver, Sender};
+use std::sync::{Arc, Mutex, MutexGuard, PoisonError};
+use std::thread::{self, JoinHandle};
+
+lazy_static! {
+    static ref REGISTRY: Mutex<Registry> = Mutex::new(Registry::default());
+}
+
+/// Every `listen` call subscribes here. All subscribers of a backend share a
+/// single source of events, a record context, an XInput2 connection or the
+/// evdev devices, which runs on its own thread as long as anyone is
+/// subscribed and broadcasts events to each subscriber's channel.
+#[derive(Default)]
+struct Registry {
+    next_id: u64,
+    /// Bumped each time a listener thread gets started, so that a thread
+    /// being torn down can't reach subscribers of its successor.
+    generation: u64,
+    /// By the backend they were asked for, `None` when left to rdev.
+    sources: HashMap<Option<Backend>, Shared>,
+    backend: Option<Backend>,
+}
+
+/// A listener thread along with its subscribers.
+struct Shared {
+    generation: u64,
+    subscribers: HashMap<u64, Sender<Result<Event, ListenError>>>,
+    listener: ListenerThread,
+}
+
+impl Registry {
+    /// The backend whose listener thread is `generation`.
+    fn backend(&self, generation: u64) -> Option<Option<Backend>> {
+        self.sources
+            .iter()
+            .find(|(_, shared)| shared.generation == generation)
+            .map(|(backend, _)| *backend)
+    }
+}
+
+fn registry() -> MutexGuard<'static, Registry> {
+    REGISTRY.lock().unwrap_or_else(PoisonError::into_inner)
+}
+
+/// Subscribes to `backend`, or to the one `set_listen_backend` picked.
+fn subscribe(backend: Option<Backend>) -> Result<Subscription, ListenError> {
+    let mut registry = registry();
+    let backend = backend.or(registry.backend);
+    if !registry.sources.contains_key(&backend) {
+        registry.generation += 1;
+        let generation = registry.generation;
+        let listener = ListenerThread::start(generation, backend)?;
+        registry.sources.insert(
+            backend,
+            Shared {
+                generation,
+                subscribers: HashMap::new(),
+                listener,
+            },
+        );
+    }
+    let id = registry.next_id;
+    registry.next_id += 1;
+    let (sender, receiver) = channel();
+    if let Some(shared) = registry.sources.get_mut(&backend) {
+        shared.subscribers.insert(id, sender);
+    }
+    Ok(Subscription { id, receiver })
+}
+
+fn unsubscribe(id: u64) {
+    let listener = {
+        let mut registry = registry();
+        let mut emptied = None;
+        for (backend, shared) in registry.sources.iter_mut() {
+            if shared.subscribers.remove(&id).is_some() {
+                if shared.subscribers.is_empty() {
+                    emptied = Some(*backend);
+                }
+                break;
+            }
+        }
+        match emptied.and_then(|backend| registry.sources.remove(&backend)) {
+            Some(shared) => shared.listener,
+            None => return,
+        }
+    };
+    // The listener thread might be waiting on the registry to broadcast, so
+    // it is stopped only once the lock is released.
+    listener.stop();
+}
+
+pub fn broadcast(generation: u64, event: Event) {
+    let registry = registry();
+    let shared = registry
+        .sources
+        .values()
+        .find(|shared| shared.generation == generation);
+    for sender in shared.iter().flat_map(|shared| shared.subscribers.values()) {
+        sender.send(Ok(event.clone())).ok();
+    }
+}
+
+/// The listener thread died on its own, every subscriber gets the error.
+fn disconnect(generation: u64, error: ListenError) {
+    let mut registry = registry();
+    let shared = match registry.backend(generation) {
+        Some(backend) => registry.sources.remove(&backend),
+        None => None,
+    };
+    // Dropping the listener thread leaves it to finish on its own.
+    for (_, sender) in shared.into_iter().flat_map(|shared| shared.subscribers) {
+        sender.send(Err(error.clone())).ok();
+    }
+}
+
+struct Subscription {
+    id: u64,
+    receiver: Receiver<Result<Event, ListenError>>,
+}
+
+impl Subscription {
+    /// Calls `callback` on every event until unsubscribed.
+    fn dispatch<T>(&self, mut callback: T) -> Result<(), ListenError>
+    where
+        T: FnMut(Event),
+    {
+        for event in self.receiver.iter() {
+            callback(event?);
+        }
+        Ok(())
+    }
+}
+
+impl Drop for Subscription {
+    fn drop(&mut self) {
+        unsubscribe(self.id);
+    }
+}
 
 pub fn listen<T>(callback: T) -> Result<(), ListenError>
 where
     T: FnMut(Event) + 'static,
 {
-    let keyboard = Keyboard::new().ok_or(ListenError::KeyboardError)?;
-
-    unsafe {
-        KEYBOARD = Some(keyboard);
-        GLOBAL_CALLBACK = Some(Box::new(callback));
-        // Open displays
-        let dpy_control = xlib::XOpenDisplay(null());
-        if dpy_control.is_null() {
-            return Err(ListenError::MissingDisplayError);
-        }
-        let extension_name = CStr::from_bytes_with_nul(b"RECORD\0")
-            .map_err(|_| ListenError::XRecordExtensionError)?;
-        let extension = xlib::XInitExtension(dpy_control, extension_name.as_ptr());
-        if extension.is_null() {
-            return Err(ListenError::XRecordExtensionError);
-        }
+    subscribe(None)?.dispatch(callback)
+}
 
-        // Prepare record range
-        let mut record_range: xrecord::XRecordRange = *xrecord::XRecordAllocRange();
-        record_range.device_events.first = xlib::KeyPress as c_uchar;
-        record_range.device_events.last = xlib::MotionNotify as c_uchar;
-
-        // Create context
-        let context = xrecord::XRecordCreateContext(
-            dpy_control,
-            0,
-            &mut RECORD_ALL_CLIENTS,
-            1,
-            &mut &mut record_range as *mut &mut xrecord::XRecordRange
-                as *mut *mut xrecord::XRecordRange,
-            1,
-        );
+/// Same as `listen`, through `backend` whatever `set_listen_backend` picked.
+/// Listeners asking for the same backend share their source.
+///
+/// ```no_run
+/// use rdev::{listen_with, Backend};
+///
+/// // This will block.
+/// listen_with(Backend::XInput2, |event| println!("{:?}", event)).unwrap();
+/// ```
+pub fn listen_with<T>(backend: Backend, callback: T) -> Result<(), ListenError>
+where
+    T: FnMut(Event) + 'static,
+{
+    subscribe(Some(backend))?.dispatch(callback)
+}
 
-        if context == 0 {
-            return Err(ListenError::RecordContextError);
-        }
+pub fn listen_with_handle<T>(callback: T) -> Result<ListenHandle, ListenError>
+where
+    T: FnMut(Event) + Send + 'static,
+{
+    let subscription = subscribe(None)?;
+    let id = subscription.id;
+    let thread = thread::spawn(move || subscription.dispatch(callback));
+    Ok(ListenHandle { id, thread })
+}
 
-        xlib::XSync(dpy_control, FALSE);
-        // Run
-        let result =
-            xrecord::XRecordEnableContext(dpy_control, context, Some(record_callback), &mut 0);
-        if result == 0 {
-            return Err(ListenError::RecordContextEnablingError);
+/// Handle on a listener started with `listen_with_handle`.
+pub struct ListenHandle {
+    id: u64,
+    thread: JoinHandle<Result<(), ListenError>>,
+}
+
+impl ListenHandle {
+    /// Stops the listener and waits for its callback to return. The source
+    /// of events gets torn down along with the last listener.
+    pub fn stop(self) -> Result<(), ListenError> {
+        unsubscribe(self.id);
+        match self.thread.join() {
+            Ok(result) => result,
+            Err(error) => panic::resume_unwind(error),
         }
     }
-    Ok(())
-}
-
-// No idea how to do that properly relevant doc lives here:
-// https://www.x.org/releases/X11R7.7/doc/libXtst/recordlib.html#Datum_Flags
-// https://docs.rs/xproto/1.1.5/xproto/struct._xEvent__bindgen_ty_1.html
-// 0.4.2: xproto was removed for some reason and contained the real structs
-// but we can't use it anymore.
-#[repr(C)]
-struct XRecordDatum {
-    type_: u8,
-    code: u8,
-    _rest: u64,
-    _1: bool,
-    _2: bool,
-    _3: bool,
-    root_x: i16,
-    root_y: i16,
-    event_x: i16,
-    event_y: i16,
-    state: u16,
-}
-
-unsafe extern "C" fn record_callback(
-    _null: *mut c_char,
-    raw_data: *mut xrecord::XRecordInterceptData,
-) {
-    let data = raw_data.as_ref().unwrap();
-    if data.category != xrecord::XRecordFromServer {
-        return;
+}
+
+/// Picks the backend used by listeners started from now on. With `None`, the
+/// default, that's RECORD, or evdev without `DISPLAY` when the `unstable_grab`
+/// feature is enabled.
+/// Listeners already running keep theirs until the last one stops.
+///
+/// ```no_run
+/// use rdev::{listen, set_listen_backend, Backend};
+///
+/// set_listen_backend(Some(Backend::XInput2));
+/// // This will block.
+/// listen(|event| println!("{:?}", event)).unwrap();
+/// ```
+pub fn set_listen_backend(backend: Option<Backend>) {
+    registry().backend = backend;
+}
+
+/// Where events come from, created on the listener thread since none of
+/// them can move across threads. There's only one at a time, so its size
+/// doesn't matter.
+#[allow(clippy::large_enum_variant)]
+enum Source {
+    Record(Recorder),
+    XInput2(RawListener),
+    #[cfg(feature = "unstable_grab")]
+    Evdev(EvdevListener),
+}
+
+impl Source {
+    /// Only evdev listens to `stop` from the start.
+    #[cfg_attr(not(feature = "unstable_grab"), allow(unused_variables))]
+    fn new(
+        generation: u64,
+        backend: Option<Backend>,
+        stop: &EventFd,
+    ) -> Result<Source, ListenError> {
+        match backend {
+            Some(Backend::Record) => Recorder::new(generation).map(Source::Record),
+            Some(Backend::XInput2) => RawListener::new().map(Source::XInput2),
+            #[cfg(feature = "unstable_grab")]
+            Some(Backend::Evdev) => EvdevListener::new(stop).map(Source::Evdev),
+            Some(_) => Err(ListenError::UnsupportedBackend),
+            #[cfg(feature = "unstable_grab")]
+            None if !has_display() => EvdevListener::new(stop).map(Source::Evdev),
+            None => Recorder::new(generation).map(Source::Record),
+        }
     }
 
-    debug_assert!(data.data_len * 4 >= std::mem::size_of::<XRecordDatum>().try_into().unwrap());
-    // Cast binary data
-    #[allow(clippy::cast_ptr_alignment)]
-    let xdatum = (data.data as *const XRecordDatum).as_ref().unwrap();
+    fn run(&mut self, generation: u64, stop: &EventFd) -> Result<(), ListenError> {
+        match self {
+            Source::Record(recorder) => recorder.run(stop),
+            Source::XInput2(listener) => listener.run(stop, |event| broadcast(generation, event)),
+            #[cfg(feature = "unstable_grab")]
+            Source::Evdev(listener) => listener.run(|event| broadcast(generation, event)),
+        }
+    }
+}
 
-    let code: c_uint = xdatum.code.into();
-    let type_: c_int = xdatum.type_.into();
+struct ListenerThread {
+    stop: Arc<EventFd>,
+    thread: JoinHandle<()>,
+}
 
-    let x = xdatum.root_x as f64;
-    let y = xdatum.root_y as f64;
+impl ListenerThread {
+    fn start(generation: u64, backend: Option<Backend>) -> Result<ListenerThread, ListenError> {
+        let stop = Arc::new(EventFd::new()?);
+        let thread_stop = stop.clone();
+        let (ready_send, ready_recv) = sync_channel(1);
+        let thread = thread::spawn(move || {
+            let mut source = match Source::new(generation, backend, &thread_stop) {
+                Ok(source) => source,
+                Err(error) => {
+                    ready_send.send(Err(error)).ok();
+                    return;
+                }
+            };
+            ready_send.send(Ok(())).ok();
+            if let Err(error) = source.run(generation, &thread_stop) {
+                disconnect(generation, error);
+            }
+        });
+        match ready_recv.recv() {
+            Ok(Ok(())) => Ok(ListenerThread { stop, thread }),
+            Ok(Err(error)) => Err(error),
+            Err(_) => match thread.join() {
+                Ok(()) => Err(ListenError::RecordContextError),
+                Err(error) => panic::resume_unwind(error),
+            },
+        }
+    }
 
-    if let Some(event) = convert(&mut KEYBOARD, code, type_, x, y) {
-        if let Some(callback) = &mut GLOBAL_CALLBACK {
-            callback(event);
+    fn stop(self) {
+        if self.stop.notify().is_ok() {
+            self.thread.join().ok();
         }
     }
-    xrecord::XRecordFreeData(raw_data);
 }
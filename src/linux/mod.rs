@@ -1,18 +1,37 @@
 extern crate libc;
 extern crate x11;
 
+mod capabilities;
 mod common;
 mod display;
 #[cfg(feature = "unstable_grab")]
 mod grab;
-mod keyboard;
 mod keycodes;
+mod keymap;
 mod listen;
 mod simulate;
+#[cfg(feature = "unstable_grab")]
+mod uinput;
+#[cfg(feature = "x11rb")]
+mod xcb;
+#[cfg(not(feature = "x11rb"))]
+mod xlib;
+
+// Xlib unless x11rb replaces it.
+#[cfg(feature = "x11rb")]
+use crate::linux::xcb as backend;
+#[cfg(not(feature = "x11rb"))]
+use crate::linux::xlib as backend;
 
+pub use crate::linux::backend::{simulate_text, Keyboard};
+pub use crate::linux::capabilities::capabilities;
 pub use crate::linux::display::display_size;
 #[cfg(feature = "unstable_grab")]
-pub use crate::linux::grab::grab;
-pub use crate::linux::keyboard::Keyboard;
-pub use crate::linux::listen::listen;
-pub use crate::linux::simulate::simulate;
+pub use crate::linux::grab::{
+    grab, grab_config, grab_emit, grab_emit_config, grab_with, grab_with_handle,
+    grab_with_handle_config, list_devices, remap, GrabHandle,
+};
+pub use crate::linux::listen::{
+    listen, listen_with, listen_with_handle, set_listen_backend, ListenHandle,
+};
+pub use crate::linux::simulate::{set_simulate_backend, simulate, simulate_with, Simulator};
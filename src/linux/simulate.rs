@@ -1,87 +1,142 @@
-use crate::linux::common::{FALSE, TRUE};
-use crate::linux::keycodes::code_from_key;
-use crate::rdev::{Button, EventType, SimulateError};
-use std::convert::TryInto;
-use std::os::raw::c_int;
-use std::ptr::null;
-use x11::xlib;
-use x11::xtest;
+use crate::linux::backend;
+#[cfg(feature = "unstable_grab")]
+use crate::linux::{common::has_display, uinput};
+use crate::rdev::{Backend, EventType, SimulateError};
+use lazy_static::lazy_static;
+use std::sync::{Mutex, PoisonError};
 
-unsafe fn send_native(event_type: &EventType, display: *mut xlib::Display) -> Option<()> {
-    let res = match event_type {
-        EventType::KeyPress(key) => {
-            let code = code_from_key(*key)?;
-            xtest::XTestFakeKeyEvent(display, code, TRUE, 0)
-        }
-        EventType::KeyRelease(key) => {
-            let code = code_from_key(*key)?;
-            xtest::XTestFakeKeyEvent(display, code, FALSE, 0)
-        }
-        EventType::ButtonPress(button) => match button {
-            Button::Left => xtest::XTestFakeButtonEvent(display, 1, TRUE, 0),
-            Button::Middle => xtest::XTestFakeButtonEvent(display, 2, TRUE, 0),
-            Button::Right => xtest::XTestFakeButtonEvent(display, 3, TRUE, 0),
-            Button::Unknown(code) => {
-                xtest::XTestFakeButtonEvent(display, (*code).try_into().ok()?, TRUE, 0)
-            }
-        },
-        EventType::ButtonRelease(button) => match button {
-            Button::Left => xtest::XTestFakeButtonEvent(display, 1, FALSE, 0),
-            Button::Middle => xtest::XTestFakeButtonEvent(display, 2, FALSE, 0),
-            Button::Right => xtest::XTestFakeButtonEvent(display, 3, FALSE, 0),
-            Button::Unknown(code) => {
-                xtest::XTestFakeButtonEvent(display, (*code).try_into().ok()?, FALSE, 0)
+lazy_static! {
+    static ref BACKEND: Mutex<Option<Backend>> = Mutex::new(None);
+}
+
+/// Picks the backend used by `simulate` and by simulators created from now
+/// on. With `None`, the default, that's XTest, or uinput without `DISPLAY`
+/// when the `unstable_grab` feature is enabled. A backend that can't simulate
+/// makes them fail.
+pub fn set_simulate_backend(backend: Option<Backend>) {
+    *BACKEND.lock().unwrap_or_else(PoisonError::into_inner) = backend;
+}
+
+/// The picked backend, uinput when there's no X server to send events to.
+fn current_backend() -> Backend {
+    match *BACKEND.lock().unwrap_or_else(PoisonError::into_inner) {
+        Some(backend) => backend,
+        #[cfg(feature = "unstable_grab")]
+        None if !has_display() => Backend::Uinput,
+        None => Backend::XTest,
+    }
+}
+
+pub fn simulate(event_type: &EventType) -> Result<(), SimulateError> {
+    simulate_with(current_backend(), event_type)
+}
+
+/// Same as `simulate`, through `backend` whatever `set_simulate_backend`
+/// picked.
+///
+/// ```no_run
+/// use rdev::{simulate_with, Backend, EventType, Key};
+///
+/// simulate_with(Backend::XTest, &EventType::KeyPress(Key::KeyS)).unwrap();
+/// ```
+pub fn simulate_with(backend: Backend, event_type: &EventType) -> Result<(), SimulateError> {
+    match backend {
+        Backend::XTest => backend::simulate(event_type),
+        #[cfg(feature = "unstable_grab")]
+        Backend::Uinput => uinput::send(event_type),
+        _ => Err(SimulateError),
+    }
+}
+
+/// Keeps a single X connection open to send many events, instead of opening
+/// one per event like `simulate` does. With uinput, every simulator shares
+/// the same virtual devices.
+///
+/// ```no_run
+/// use rdev::{EventType, Simulator};
+///
+/// let mut simulator = Simulator::new().unwrap();
+/// let path = (0..480)
+///     .map(|pixel| EventType::MouseMove {
+///         x: pixel as f64,
+///         y: pixel as f64,
+///     })
+///     .collect::<Vec<_>>();
+/// simulator.send_batch(&path).unwrap();
+/// simulator.flush().unwrap();
+/// ```
+pub struct Simulator {
+    inner: Inner,
+}
+
+enum Inner {
+    /// Boxed since the x11rb connection is large.
+    XTest(Box<backend::Simulator>),
+    #[cfg(feature = "unstable_grab")]
+    Uinput,
+}
+
+impl Simulator {
+    pub fn new() -> Result<Simulator, SimulateError> {
+        let inner = match current_backend() {
+            Backend::XTest => Inner::XTest(Box::new(backend::Simulator::new()?)),
+            #[cfg(feature = "unstable_grab")]
+            Backend::Uinput => {
+                uinput::open()?;
+                Inner::Uinput
             }
-        },
-        EventType::MouseMove { x, y } => {
-            //TODO: replace with clamp if it is stabalized
-            let x = if x.is_finite() {
-                x.min(c_int::max_value().into())
-                    .max(c_int::min_value().into())
-                    .round() as c_int
-            } else {
-                0
-            };
-            let y = if y.is_finite() {
-                y.min(c_int::max_value().into())
-                    .max(c_int::min_value().into())
-                    .round() as c_int
-            } else {
-                0
-            };
-            xtest::XTestFakeMotionEvent(display, 0, x, y, 0)
-            //     xlib::XWarpPointer(display, 0, root, 0, 0, 0, 0, *x as i32, *y as i32);
+            _ => return Err(SimulateError),
+        };
+        Ok(Simulator { inner })
+    }
+
+    /// Sends the event out without waiting for the X server to process it.
+    pub fn send(&mut self, event_type: &EventType) -> Result<(), SimulateError> {
+        match &mut self.inner {
+            Inner::XTest(simulator) => simulator.send(event_type),
+            #[cfg(feature = "unstable_grab")]
+            Inner::Uinput => uinput::send(event_type),
         }
-        EventType::Wheel { delta_y, .. } => {
-            let code = if *delta_y > 0 { 4 } else { 5 };
-            xtest::XTestFakeButtonEvent(display, code, TRUE, 0)
-                & xtest::XTestFakeButtonEvent(display, code, FALSE, 0)
+    }
+
+    /// Sends all events in order, with a single round of writes to the X server.
+    /// Stops at the first event that can't be sent.
+    pub fn send_batch(&mut self, event_types: &[EventType]) -> Result<(), SimulateError> {
+        match &mut self.inner {
+            Inner::XTest(simulator) => simulator.send_batch(event_types),
+            #[cfg(feature = "unstable_grab")]
+            Inner::Uinput => uinput::send_batch(event_types),
         }
-    };
-    if res == 0 {
-        None
-    } else {
-        Some(())
     }
-}
 
-pub fn simulate(event_type: &EventType) -> Result<(), SimulateError> {
-    unsafe {
-        let dpy = xlib::XOpenDisplay(null());
-        if dpy.is_null() {
-            return Err(SimulateError);
+    /// Moves the pointer by `dx` and `dy` pixels, from wherever it is.
+    pub fn move_by(&mut self, dx: i32, dy: i32) -> Result<(), SimulateError> {
+        match &mut self.inner {
+            Inner::XTest(simulator) => simulator.move_by(dx, dy),
+            #[cfg(feature = "unstable_grab")]
+            Inner::Uinput => uinput::move_by(dx, dy),
         }
-        match send_native(event_type, dpy) {
-            Some(_) => {
-                xlib::XFlush(dpy);
-                xlib::XSync(dpy, 0);
-                xlib::XCloseDisplay(dpy);
-                Ok(())
-            }
-            None => {
-                xlib::XCloseDisplay(dpy);
-                Err(SimulateError)
-            }
+    }
+
+    /// Types `text` with the current layout. Characters that no key can type
+    /// get temporarily mapped on a spare keycode, which is restored once the
+    /// whole text got typed. Only XTest can do it, uinput knows nothing of the
+    /// layout.
+    pub fn send_text(&mut self, text: &str) -> Result<(), SimulateError> {
+        match &mut self.inner {
+            Inner::XTest(simulator) => simulator.send_text(text),
+            #[cfg(feature = "unstable_grab")]
+            Inner::Uinput => Err(SimulateError),
+        }
+    }
+
+    /// Waits until every event sent so far got processed. uinput events reach
+    /// the kernel as they are sent.
+    pub fn flush(&mut self) -> Result<(), SimulateError> {
+        match &mut self.inner {
+            Inner::XTest(simulator) => simulator.flush(),
+            #[cfg(feature = "unstable_grab")]
+            Inner::Uinput => Ok(()),
         }
     }
 }
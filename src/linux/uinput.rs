@@ -0,0 +1,201 @@
+use crate::linux::backend::Display;
+use crate::linux::grab::{framebuffer_size, rdev_event_to_evdev_event, rdev_keys};
+use crate::rdev::{EventType, SimulateError};
+use evdev_rs::enums::{EventCode, EV_ABS, EV_KEY, EV_REL, EV_SYN};
+use evdev_rs::{AbsInfo, Device, InputEvent, TimeVal, UInputDevice};
+use lazy_static::lazy_static;
+use std::convert::TryInto;
+use std::io;
+use std::sync::{Mutex, PoisonError};
+use std::thread;
+use std::time::Duration;
+
+lazy_static! {
+    static ref VIRTUAL_INPUT: Mutex<Option<VirtualInput>> = Mutex::new(None);
+}
+
+static NAME: &str = "rdev virtual input";
+static POINTER_NAME: &str = "rdev virtual pointer";
+/// Hi-res wheels count 120 per notch.
+const HI_RES_NOTCH: i32 = 120;
+
+/// The uinput devices events get simulated through, created on first use
+/// and kept for as long as the process runs. The keyboard and mouse moves
+/// relatively, absolute moves go through a pointer of their own, since
+/// libinput doesn't handle both on one device.
+struct VirtualInput {
+    keyboard: UInputDevice,
+    /// Along with the screen size its axes span, if there's a screen.
+    pointer: Option<(UInputDevice, (f64, f64))>,
+}
+
+// uinput devices are file descriptors libevdev writes to, only ever used
+// behind VIRTUAL_INPUT's lock.
+unsafe impl Send for VirtualInput {}
+
+impl VirtualInput {
+    fn new() -> io::Result<VirtualInput> {
+        let device = new_device(NAME)?;
+        for key in rdev_keys() {
+            device.enable(&EventCode::EV_KEY(key))?;
+        }
+        for axis in &[
+            EV_REL::REL_X,
+            EV_REL::REL_Y,
+            EV_REL::REL_WHEEL,
+            EV_REL::REL_HWHEEL,
+            EV_REL::REL_WHEEL_HI_RES,
+            EV_REL::REL_HWHEEL_HI_RES,
+        ] {
+            device.enable(&EventCode::EV_REL(axis.clone()))?;
+        }
+        let keyboard = UInputDevice::create_from_device(&device)?;
+        let pointer = match screen_size() {
+            Some(size) => Some((absolute_pointer(size)?, size)),
+            None => None,
+        };
+        // The compositor or the X server opens new devices once udev is done
+        // with them, events sent before that get lost.
+        thread::sleep(Duration::from_millis(200));
+        Ok(VirtualInput { keyboard, pointer })
+    }
+
+    fn send(&self, event_type: &EventType) -> Option<()> {
+        let time = TimeVal::new(0, 0);
+        match event_type {
+            EventType::MouseMove { x, y } => {
+                let (pointer, (width, height)) = self.pointer.as_ref()?;
+                let axis = |axis, position: f64, size: f64| {
+                    let value = position.clamp(0.0, size).round() as i32;
+                    InputEvent::new(&time, &EventCode::EV_ABS(axis), value)
+                };
+                write_frame(
+                    pointer,
+                    &[
+                        axis(EV_ABS::ABS_X, *x, *width),
+                        axis(EV_ABS::ABS_Y, *y, *height),
+                    ],
+                )
+            }
+            EventType::Wheel { delta_x, delta_y } => {
+                let mut events = vec![];
+                let mut wheel = |delta: i64, axis, hi_res_axis| -> Option<()> {
+                    if delta != 0 {
+                        let delta: i32 = delta.try_into().ok()?;
+                        let hi_res = delta.checked_mul(HI_RES_NOTCH)?;
+                        events.push(InputEvent::new(&time, &EventCode::EV_REL(axis), delta));
+                        events.push(InputEvent::new(
+                            &time,
+                            &EventCode::EV_REL(hi_res_axis),
+                            hi_res,
+                        ));
+                    }
+                    Some(())
+                };
+                wheel(*delta_y, EV_REL::REL_WHEEL, EV_REL::REL_WHEEL_HI_RES)?;
+                wheel(*delta_x, EV_REL::REL_HWHEEL, EV_REL::REL_HWHEEL_HI_RES)?;
+                write_frame(&self.keyboard, &events)
+            }
+            event_type => {
+                let event = rdev_event_to_evdev_event(event_type, &time)?;
+                write_frame(&self.keyboard, &[event])
+            }
+        }
+    }
+
+    fn move_by(&self, dx: i32, dy: i32) -> Option<()> {
+        let time = TimeVal::new(0, 0);
+        let mut events = vec![];
+        if dx != 0 {
+            events.push(InputEvent::new(
+                &time,
+                &EventCode::EV_REL(EV_REL::REL_X),
+                dx,
+            ));
+        }
+        if dy != 0 {
+            events.push(InputEvent::new(
+                &time,
+                &EventCode::EV_REL(EV_REL::REL_Y),
+                dy,
+            ));
+        }
+        write_frame(&self.keyboard, &events)
+    }
+}
+
+fn new_device(name: &str) -> io::Result<Device> {
+    let device = Device::new().ok_or_else(|| io::Error::from(io::ErrorKind::OutOfMemory))?;
+    device.set_name(name);
+    Ok(device)
+}
+
+/// A pointer whose axes span the screen, one unit per pixel.
+fn absolute_pointer((width, height): (f64, f64)) -> io::Result<UInputDevice> {
+    let device = new_device(POINTER_NAME)?;
+    // A button makes it a pointer, rather than a touchscreen or a joystick.
+    device.enable(&EventCode::EV_KEY(EV_KEY::BTN_LEFT))?;
+    for (axis, size) in &[(EV_ABS::ABS_X, width), (EV_ABS::ABS_Y, height)] {
+        let info = AbsInfo {
+            value: 0,
+            minimum: 0,
+            maximum: *size as i32,
+            fuzz: 0,
+            flat: 0,
+            resolution: 0,
+        };
+        device.enable_event_code(&EventCode::EV_ABS(axis.clone()), Some(&info))?;
+    }
+    UInputDevice::create_from_device(&device)
+}
+
+fn screen_size() -> Option<(f64, f64)> {
+    Display::new()
+        .and_then(|display| display.get_size())
+        .map(|(width, height)| (width as f64, height as f64))
+        .or_else(framebuffer_size)
+}
+
+/// Writes `events` as a single frame, the kernel stamps their time.
+fn write_frame(device: &UInputDevice, events: &[InputEvent]) -> Option<()> {
+    let time = TimeVal::new(0, 0);
+    for event in events {
+        device.write_event(event).ok()?;
+    }
+    let report = InputEvent::new(&time, &EventCode::EV_SYN(EV_SYN::SYN_REPORT), 0);
+    device.write_event(&report).ok()
+}
+
+/// Runs `func` on the virtual devices, creating them first if need be.
+fn with_virtual_input<F>(func: F) -> Result<(), SimulateError>
+where
+    F: FnOnce(&VirtualInput) -> Option<()>,
+{
+    let mut virtual_input = VIRTUAL_INPUT.lock().unwrap_or_else(PoisonError::into_inner);
+    if virtual_input.is_none() {
+        *virtual_input = Some(VirtualInput::new().map_err(|_| SimulateError)?);
+    }
+    virtual_input.as_ref().and_then(func).ok_or(SimulateError)
+}
+
+/// Creates the virtual devices, if they don't exist yet.
+pub fn open() -> Result<(), SimulateError> {
+    with_virtual_input(|_| Some(()))
+}
+
+pub fn send(event_type: &EventType) -> Result<(), SimulateError> {
+    with_virtual_input(|virtual_input| virtual_input.send(event_type))
+}
+
+/// Stops at the first event that can't be sent.
+pub fn send_batch(event_types: &[EventType]) -> Result<(), SimulateError> {
+    with_virtual_input(|virtual_input| {
+        event_types
+            .iter()
+            .try_for_each(|event_type| virtual_input.send(event_type))
+    })
+}
+
+pub fn move_by(dx: i32, dy: i32) -> Result<(), SimulateError> {
+    with_virtual_input(|virtual_input| virtual_input.move_by(dx, dy))
+}
@@ -0,0 +1,85 @@
+#[cfg(feature = "unstable_grab")]
+use crate::linux::common::modifiers_from_state;
+#[cfg(feature = "unstable_grab")]
+use crate::rdev::Modifiers;
+#[cfg(feature = "unstable_grab")]
+use std::convert::TryInto;
+use x11rb::connection::{Connection, RequestConnection};
+use x11rb::protocol::record::{self, ConnectionExt as _};
+use x11rb::protocol::xinput::{self, ConnectionExt as _};
+#[cfg(feature = "unstable_grab")]
+use x11rb::protocol::xproto::{ConnectionExt as _, QueryPointerReply};
+use x11rb::protocol::xtest;
+use x11rb::rust_connection::RustConnection;
+
+pub struct Display {
+    conn: RustConnection,
+    screen: usize,
+}
+
+impl Display {
+    pub fn new() -> Option<Display> {
+        let (conn, screen) = RustConnection::connect(None).ok()?;
+        Some(Display { conn, screen })
+    }
+
+    pub fn get_size(&self) -> Option<(u64, u64)> {
+        let screen = self.conn.setup().roots.get(self.screen)?;
+        Some((
+            screen.width_in_pixels.into(),
+            screen.height_in_pixels.into(),
+        ))
+    }
+
+    /// Whether the X server has XInput 2.1, which raw events need.
+    pub fn has_xinput2(&self) -> bool {
+        self.has_extension(xinput::X11_EXTENSION_NAME)
+            && self
+                .conn
+                .xinput_xi_query_version(2, 1)
+                .ok()
+                .and_then(|cookie| cookie.reply().ok())
+                .is_some_and(|version| (version.major_version, version.minor_version) >= (2, 1))
+    }
+
+    pub fn has_record(&self) -> bool {
+        self.has_extension(record::X11_EXTENSION_NAME)
+            && self
+                .conn
+                .record_query_version(1, 13)
+                .ok()
+                .and_then(|cookie| cookie.reply().ok())
+                .is_some()
+    }
+
+    pub fn has_xtest(&self) -> bool {
+        self.has_extension(xtest::X11_EXTENSION_NAME)
+    }
+
+    fn has_extension(&self, name: &'static str) -> bool {
+        self.conn
+            .extension_information(name)
+            .is_ok_and(|extension| extension.is_some())
+    }
+
+    #[cfg(feature = "unstable_grab")]
+    pub fn get_mouse_pos(&self) -> Option<(u64, u64)> {
+        let pointer = self.query_pointer()?;
+        Some((
+            pointer.root_x.try_into().ok()?,
+            pointer.root_y.try_into().ok()?,
+        ))
+    }
+
+    #[cfg(feature = "unstable_grab")]
+    pub fn get_modifiers(&self) -> Option<Modifiers> {
+        let pointer = self.query_pointer()?;
+        Some(modifiers_from_state(u16::from(pointer.mask).into()))
+    }
+
+    #[cfg(feature = "unstable_grab")]
+    fn query_pointer(&self) -> Option<QueryPointerReply> {
+        let root = self.conn.setup().roots.get(self.screen)?.root;
+        self.conn.query_pointer(root).ok()?.reply().ok()
+    }
+}
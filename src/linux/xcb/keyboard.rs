@@ -0,0 +1,186 @@
+use crate::linux::keycodes::code_from_key;
+use crate::rdev::{EventType, KeyboardState};
+use std::env;
+use std::ffi::OsString;
+use x11rb::connection::Connection;
+use x11rb::protocol::xproto::{AtomEnum, ConnectionExt, Window};
+use x11rb::rust_connection::RustConnection;
+use xkbcommon::xkb;
+use xkbcommon::xkb::compose;
+
+/// Resolves key names with xkbcommon, from the layout the X server uses.
+/// Unlike Xlib, it can be used from any thread. Without an X server, the
+/// layout comes from xkbcommon's defaults, which the `XKB_DEFAULT_LAYOUT`
+/// and similar variables change.
+pub struct Keyboard {
+    keymap: xkb::Keymap,
+    state: xkb::State,
+    /// Dead keys and Compose sequences, when the locale has a Compose table.
+    compose: Option<compose::State>,
+}
+
+impl Keyboard {
+    pub fn new() -> Option<Keyboard> {
+        let names = RustConnection::connect(None)
+            .ok()
+            .and_then(|(conn, screen)| {
+                let root = conn.setup().roots.get(screen)?.root;
+                rules_names(&conn, root)
+            });
+        let context = xkb::Context::new(xkb::CONTEXT_NO_FLAGS);
+        // Missing names get xkbcommon's defaults.
+        let name = |index: usize| names.as_ref().map_or("", |names| &names[index]);
+        let keymap = xkb::Keymap::new_from_names(
+            &context,
+            name(0),
+            name(1),
+            name(2),
+            name(3),
+            names.as_ref().map(|names| names[4].clone()),
+            xkb::KEYMAP_COMPILE_NO_FLAGS,
+        )?;
+        let state = xkb::State::new(&keymap);
+        let compose =
+            compose::Table::new_from_locale(&context, &locale(), compose::COMPILE_NO_FLAGS)
+                .ok()
+                .map(|table| compose::State::new(&table, compose::STATE_NO_FLAGS));
+        Some(Keyboard {
+            keymap,
+            state,
+            compose,
+        })
+    }
+
+    /// What a key giving `keysym` types, going through Compose sequences.
+    fn name(&mut self, keysym: xkb::Keysym) -> Option<String> {
+        if let Some(compose) = &mut self.compose {
+            if compose.feed(keysym) == compose::FeedResult::Accepted {
+                match compose.status() {
+                    compose::Status::Composing => return None,
+                    compose::Status::Composed => {
+                        let composed = compose.utf8();
+                        compose.reset();
+                        return composed;
+                    }
+                    compose::Status::Cancelled => {
+                        compose.reset();
+                        return None;
+                    }
+                    compose::Status::Nothing => {}
+                }
+            }
+        }
+        // key_get_utf8 would turn ctrl+a into a control character, the name
+        // stays the letter like with Xlib. Keys typing nothing, like arrows or
+        // function keys, have no name.
+        Some(xkb::keysym_to_utf8(keysym)).filter(|name| !name.is_empty())
+    }
+}
+
+/// Rules, model, layout, variant and options the X server compiled its
+/// keymap from, as setxkbmap leaves them on the root window.
+fn rules_names(conn: &RustConnection, root: Window) -> Option<Vec<String>> {
+    let atom = conn
+        .intern_atom(true, b"_XKB_RULES_NAMES")
+        .ok()?
+        .reply()
+        .ok()?
+        .atom;
+    if atom == x11rb::NONE {
+        return None;
+    }
+    let property = conn
+        .get_property(false, root, atom, AtomEnum::STRING, 0, 1024)
+        .ok()?
+        .reply()
+        .ok()?;
+    let mut names = property
+        .value
+        .split(|byte| *byte == 0)
+        .map(|name| String::from_utf8_lossy(name).into_owned())
+        .collect::<Vec<_>>();
+    names.resize(5, String::new());
+    Some(names)
+}
+
+/// The locale picking the Compose table, like setlocale would.
+fn locale() -> OsString {
+    ["LC_ALL", "LC_CTYPE", "LANG"]
+        .iter()
+        .filter_map(env::var_os)
+        .find(|locale| !locale.is_empty())
+        .unwrap_or_else(|| OsString::from("C"))
+}
+
+impl KeyboardState for Keyboard {
+    fn add(&mut self, event_type: &EventType) -> Option<String> {
+        match event_type {
+            EventType::KeyPress(key) => {
+                let keycode = xkb::Keycode::new(code_from_key(*key)?);
+                // The key types with the state from before its own press.
+                let keysym = self.state.key_get_one_sym(keycode);
+                let name = if keysym.is_modifier_key() {
+                    None
+                } else {
+                    self.name(keysym)
+                };
+                self.state.update_key(keycode, xkb::KeyDirection::Down);
+                name
+            }
+            EventType::KeyRelease(key) => {
+                if let Some(keycode) = code_from_key(*key) {
+                    self.state
+                        .update_key(xkb::Keycode::new(keycode), xkb::KeyDirection::Up);
+                }
+                None
+            }
+            _ => None,
+        }
+    }
+
+    fn reset(&mut self) {
+        self.state = xkb::State::new(&self.keymap);
+        // Drops any pending dead key or Compose sequence.
+        if let Some(compose) = &mut self.compose {
+            compose.reset();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::rdev::Key;
+    use std::thread;
+
+    #[test]
+    #[ignore]
+    /// Unlike the Xlib one, this keyboard can live on any thread. Needs an X
+    /// server and libxkbcommon.
+    fn test_thread_safety() {
+        let threads = (0..2)
+            .map(|_| {
+                thread::spawn(|| {
+                    let mut keyboard = Keyboard::new().unwrap();
+                    keyboard.add(&EventType::KeyPress(Key::KeyS))
+                })
+            })
+            .collect::<Vec<_>>();
+        for thread in threads {
+            assert_eq!(
+                thread.join().unwrap(),
+                Some("s".to_string()),
+                "This test should pass only on Qwerty layout !"
+            );
+        }
+    }
+
+    #[test]
+    #[ignore]
+    /// Needs an X server and libxkbcommon.
+    fn test_no_name() {
+        let mut keyboard = Keyboard::new().unwrap();
+        assert_eq!(keyboard.add(&EventType::KeyPress(Key::UpArrow)), None);
+        assert_eq!(keyboard.add(&EventType::KeyPress(Key::F1)), None);
+    }
+}
@@ -0,0 +1,11 @@
+mod display;
+mod keyboard;
+mod record;
+mod simulate;
+mod xinput;
+
+pub use crate::linux::xcb::display::Display;
+pub use crate::linux::xcb::keyboard::Keyboard;
+pub use crate::linux::xcb::record::Recorder;
+pub use crate::linux::xcb::simulate::{simulate, simulate_text, Simulator};
+pub use crate::linux::xcb::xinput::RawListener;
@@ -0,0 +1,157 @@
+use crate::linux::common::{convert, EventFd};
+use crate::linux::listen::broadcast;
+use crate::linux::xcb::Keyboard;
+use crate::rdev::ListenError;
+use std::io;
+use std::os::unix::io::AsRawFd;
+use std::thread;
+use x11rb::connection::{Connection, RequestConnection};
+use x11rb::errors::ReplyError;
+use x11rb::protocol::record::{self, ConnectionExt as _};
+use x11rb::protocol::xproto::{self, KeyPressEvent};
+use x11rb::rust_connection::RustConnection;
+use x11rb::x11_utils::TryParse;
+
+/// Category of the replies carrying events sent by the X server.
+const FROM_SERVER: u8 = 0;
+
+/// Owns the record context and the two X connections it needs: the control
+/// one creates and disables the context, the data one receives the
+/// intercepted events.
+pub struct Recorder {
+    control: RustConnection,
+    data: RustConnection,
+    context: record::Context,
+    keyboard: Keyboard,
+    generation: u64,
+}
+
+impl Recorder {
+    pub fn new(generation: u64) -> Result<Recorder, ListenError> {
+        let keyboard = Keyboard::new().ok_or(ListenError::KeyboardError)?;
+        let (control, _) =
+            RustConnection::connect(None).map_err(|_| ListenError::MissingDisplayError)?;
+        let (data, _) =
+            RustConnection::connect(None).map_err(|_| ListenError::MissingDisplayError)?;
+
+        let extension = control
+            .extension_information(record::X11_EXTENSION_NAME)
+            .map_err(|_| ListenError::XRecordExtensionError)?;
+        if extension.is_none() {
+            return Err(ListenError::XRecordExtensionError);
+        }
+
+        let context = control
+            .generate_id()
+            .map_err(|_| ListenError::RecordContextError)?;
+        let range = record::Range {
+            device_events: record::Range8 {
+                first: xproto::KEY_PRESS_EVENT,
+                last: xproto::MOTION_NOTIFY_EVENT,
+            },
+            ..Default::default()
+        };
+        control
+            .record_create_context(context, 0, &[record::CS::ALL_CLIENTS.into()], &[range])
+            .map_err(|_| ListenError::RecordContextError)?
+            .check()
+            .map_err(|_| ListenError::RecordContextError)?;
+        Ok(Recorder {
+            control,
+            data,
+            context,
+            keyboard,
+            generation,
+        })
+    }
+
+    /// Broadcasts events until `stop` gets notified.
+    pub fn run(&mut self, stop: &EventFd) -> Result<(), ListenError> {
+        let Recorder {
+            control,
+            data,
+            context,
+            keyboard,
+            generation,
+        } = self;
+        // Replies to the enabling request keep coming until the context gets
+        // disabled, from the control connection.
+        thread::scope(|scope| {
+            let watcher = scope.spawn(|| {
+                wait(stop).ok();
+                if control.record_disable_context(*context).is_ok() {
+                    control.flush().ok();
+                }
+            });
+            let result = receive(data, *context, keyboard, *generation);
+            // The data connection might have died first.
+            stop.notify().ok();
+            watcher.join().ok();
+            result
+        })
+    }
+}
+
+impl Drop for Recorder {
+    fn drop(&mut self) {
+        if self.control.record_free_context(self.context).is_ok() {
+            self.control.flush().ok();
+        }
+    }
+}
+
+fn receive(
+    data: &RustConnection,
+    context: record::Context,
+    keyboard: &mut Keyboard,
+    generation: u64,
+) -> Result<(), ListenError> {
+    let replies = data
+        .record_enable_context(context)
+        .map_err(|_| ListenError::RecordContextEnablingError)?;
+    for reply in replies {
+        let reply = reply.map_err(|error| match error {
+            ReplyError::X11Error(_) => ListenError::RecordContextEnablingError,
+            ReplyError::ConnectionError(_) => ListenError::MissingDisplayError,
+        })?;
+        if reply.category != FROM_SERVER {
+            continue;
+        }
+        // Key, button and motion events share the same layout.
+        for datum in reply.data.chunks_exact(32) {
+            let event = match KeyPressEvent::try_parse(datum) {
+                Ok((event, _)) => event,
+                Err(_) => continue,
+            };
+            if let Some(event) = convert(
+                keyboard,
+                event.detail.into(),
+                (event.response_type & 0x7f).into(),
+                event.root_x.into(),
+                event.root_y.into(),
+                u16::from(event.state).into(),
+            ) {
+                broadcast(generation, event);
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Blocks until `stop` gets notified.
+fn wait(stop: &EventFd) -> io::Result<()> {
+    let mut fd = libc::pollfd {
+        fd: stop.as_raw_fd(),
+        events: libc::POLLIN,
+        revents: 0,
+    };
+    loop {
+        if unsafe { libc::poll(&mut fd, 1, -1) } >= 0 {
+            return Ok(());
+        }
+        let error = io::Error::last_os_error();
+        if error.kind() != io::ErrorKind::Interrupted {
+            return Err(error);
+        }
+    }
+}
@@ -0,0 +1,256 @@
+use crate::linux::keycodes::code_from_key;
+use crate::linux::keymap::{keysym_from_char, Keymap, NO_SYMBOL, REMAP_DELAY};
+use crate::rdev::{Button, EventType, SimulateError};
+use std::convert::TryInto;
+use std::os::raw::c_uint;
+use std::thread;
+use x11::keysym;
+use x11::xlib::KeySym;
+use x11rb::connection::Connection;
+use x11rb::protocol::xproto::{self, ConnectionExt as _, Window};
+use x11rb::protocol::xtest::ConnectionExt as _;
+use x11rb::rust_connection::RustConnection;
+use x11rb::CURRENT_TIME;
+
+/// Keeps a single X connection open to send many events through XTest.
+pub struct Simulator {
+    conn: RustConnection,
+    root: Window,
+}
+
+impl Simulator {
+    pub fn new() -> Result<Simulator, SimulateError> {
+        let (conn, screen) = RustConnection::connect(None).map_err(|_| SimulateError)?;
+        let root = conn.setup().roots.get(screen).ok_or(SimulateError)?.root;
+        Ok(Simulator { conn, root })
+    }
+
+    fn fake_input(&self, type_: u8, detail: u8, x: i16, y: i16) -> Option<()> {
+        self.conn
+            .xtest_fake_input(type_, detail, CURRENT_TIME, self.root, x, y, 0)
+            .ok()?;
+        Some(())
+    }
+
+    fn send_native(&self, event_type: &EventType) -> Option<()> {
+        match event_type {
+            EventType::KeyPress(key) => {
+                let code = code_from_key(*key)?.try_into().ok()?;
+                self.fake_input(xproto::KEY_PRESS_EVENT, code, 0, 0)
+            }
+            EventType::KeyRelease(key) => {
+                let code = code_from_key(*key)?.try_into().ok()?;
+                self.fake_input(xproto::KEY_RELEASE_EVENT, code, 0, 0)
+            }
+            EventType::ButtonPress(button) => {
+                self.fake_input(xproto::BUTTON_PRESS_EVENT, button_code(button), 0, 0)
+            }
+            EventType::ButtonRelease(button) => {
+                self.fake_input(xproto::BUTTON_RELEASE_EVENT, button_code(button), 0, 0)
+            }
+            EventType::MouseMove { x, y } => {
+                // The protocol carries 16 bits coordinates.
+                let clamp = |value: f64| {
+                    if value.is_finite() {
+                        value.clamp(i16::MIN.into(), i16::MAX.into()).round() as i16
+                    } else {
+                        0
+                    }
+                };
+                self.fake_input(xproto::MOTION_NOTIFY_EVENT, 0, clamp(*x), clamp(*y))
+            }
+            EventType::Wheel { delta_y, .. } => {
+                let code = if *delta_y > 0 { 4 } else { 5 };
+                self.fake_input(xproto::BUTTON_PRESS_EVENT, code, 0, 0)?;
+                self.fake_input(xproto::BUTTON_RELEASE_EVENT, code, 0, 0)
+            }
+        }
+    }
+
+    /// Sends the event out without waiting for the X server to process it.
+    pub fn send(&mut self, event_type: &EventType) -> Result<(), SimulateError> {
+        self.send_native(event_type).ok_or(SimulateError)?;
+        self.conn.flush().map_err(|_| SimulateError)
+    }
+
+    /// Moves the pointer by `dx` and `dy` pixels.
+    pub fn move_by(&mut self, dx: i32, dy: i32) -> Result<(), SimulateError> {
+        let dx = dx.try_into().map_err(|_| SimulateError)?;
+        let dy = dy.try_into().map_err(|_| SimulateError)?;
+        // A detail of 1 makes the motion relative.
+        self.fake_input(xproto::MOTION_NOTIFY_EVENT, 1, dx, dy)
+            .ok_or(SimulateError)?;
+        self.conn.flush().map_err(|_| SimulateError)
+    }
+
+    /// Sends all events in order, with a single round of writes to the X server.
+    /// Stops at the first event that can't be sent.
+    pub fn send_batch(&mut self, event_types: &[EventType]) -> Result<(), SimulateError> {
+        let result = event_types
+            .iter()
+            .try_for_each(|event_type| self.send_native(event_type));
+        self.conn.flush().map_err(|_| SimulateError)?;
+        result.ok_or(SimulateError)
+    }
+
+    /// Types `text` with the current layout. Characters that no key can type
+    /// get temporarily mapped on a spare keycode, which is restored shortly
+    /// after the whole text got typed. CapsLock gets turned off while typing.
+    pub fn send_text(&mut self, text: &str) -> Result<(), SimulateError> {
+        let mut keymap = self.keymap().ok_or(SimulateError)?;
+        let shift = keymap
+            .keycode(keysym::XK_Shift_L.into())
+            .ok_or(SimulateError)?;
+        let level3 = keymap.keycode(keysym::XK_ISO_Level3_Shift.into());
+        let spares = keymap.spare_keycodes();
+        let mut remapped = Vec::new();
+        let caps_lock = if self.caps_locked().ok_or(SimulateError)? {
+            keymap.keycode(keysym::XK_Caps_Lock.into())
+        } else {
+            None
+        };
+        if let Some(caps_lock) = caps_lock {
+            self.type_key(caps_lock, &[]).ok_or(SimulateError)?;
+        }
+
+        let result = text.chars().try_for_each(|ch| {
+            let keysym = keysym_from_char(ch);
+            let (keycode, level) = match keymap.find(keysym, level3.is_some()) {
+                Some(found) => found,
+                None => {
+                    // Once every spare keycode is in use, wait for the
+                    // characters typed so far before giving them back.
+                    if remapped.len() == spares.len() {
+                        self.restore(&mut keymap, &mut remapped)?;
+                    }
+                    let keycode = *spares.get(remapped.len())?;
+                    self.remap(&mut keymap, keycode, keysym)?;
+                    remapped.push(keycode);
+                    (keycode, 0)
+                }
+            };
+            let mut modifiers = Vec::new();
+            if level >= 2 {
+                modifiers.push(level3?);
+            }
+            if level % 2 == 1 {
+                modifiers.push(shift);
+            }
+            self.type_key(keycode, &modifiers)
+        });
+
+        if let Some(caps_lock) = caps_lock {
+            self.type_key(caps_lock, &[]).ok_or(SimulateError)?;
+        }
+        self.restore(&mut keymap, &mut remapped)
+            .ok_or(SimulateError)?;
+        self.conn.flush().map_err(|_| SimulateError)?;
+        result.ok_or(SimulateError)
+    }
+
+    /// Waits until the X server has processed every event sent so far.
+    pub fn flush(&mut self) -> Result<(), SimulateError> {
+        self.sync().ok_or(SimulateError)
+    }
+
+    fn sync(&self) -> Option<()> {
+        self.conn.get_input_focus().ok()?.reply().ok()?;
+        Some(())
+    }
+
+    /// Presses `modifiers`, types `keycode`, then releases `modifiers`.
+    fn type_key(&self, keycode: c_uint, modifiers: &[c_uint]) -> Option<()> {
+        let code = |keycode: &c_uint| (*keycode).try_into().ok();
+        for modifier in modifiers {
+            self.fake_input(xproto::KEY_PRESS_EVENT, code(modifier)?, 0, 0)?;
+        }
+        self.fake_input(xproto::KEY_PRESS_EVENT, code(&keycode)?, 0, 0)?;
+        self.fake_input(xproto::KEY_RELEASE_EVENT, code(&keycode)?, 0, 0)?;
+        for modifier in modifiers.iter().rev() {
+            self.fake_input(xproto::KEY_RELEASE_EVENT, code(modifier)?, 0, 0)?;
+        }
+        Some(())
+    }
+
+    /// Whether CapsLock is on.
+    fn caps_locked(&self) -> Option<bool> {
+        let pointer = self.conn.query_pointer(self.root).ok()?.reply().ok()?;
+        Some(pointer.mask.contains(xproto::KeyButMask::LOCK))
+    }
+
+    /// Gives the `remapped` spare keycodes back, once the X server got the
+    /// keys typed with them and clients had the time to look them up.
+    fn restore(&self, keymap: &mut Keymap, remapped: &mut Vec<c_uint>) -> Option<()> {
+        if remapped.is_empty() {
+            return Some(());
+        }
+        self.sync()?;
+        thread::sleep(REMAP_DELAY);
+        for keycode in remapped.drain(..) {
+            self.remap(keymap, keycode, NO_SYMBOL)?;
+        }
+        Some(())
+    }
+
+    fn keymap(&self) -> Option<Keymap> {
+        let setup = self.conn.setup();
+        let count = setup.max_keycode - setup.min_keycode + 1;
+        let mapping = self
+            .conn
+            .get_keyboard_mapping(setup.min_keycode, count)
+            .ok()?
+            .reply()
+            .ok()?;
+        Some(Keymap::new(
+            setup.min_keycode.into(),
+            mapping.keysyms_per_keycode.into(),
+            mapping.keysyms.into_iter().map(KeySym::from).collect(),
+        ))
+    }
+
+    fn remap(&self, keymap: &mut Keymap, keycode: c_uint, keysym: KeySym) -> Option<()> {
+        let keysyms = keymap
+            .remap(keycode, keysym)
+            .iter()
+            .map(|keysym| (*keysym).try_into().ok())
+            .collect::<Option<Vec<u32>>>()?;
+        self.conn
+            .change_keyboard_mapping(
+                1,
+                keycode.try_into().ok()?,
+                keysyms.len().try_into().ok()?,
+                &keysyms,
+            )
+            .ok()?;
+        Some(())
+    }
+}
+
+fn button_code(button: &Button) -> u8 {
+    match button {
+        Button::Left => 1,
+        Button::Middle => 2,
+        Button::Right => 3,
+        Button::Unknown(code) => *code,
+    }
+}
+
+pub fn simulate(event_type: &EventType) -> Result<(), SimulateError> {
+    let mut simulator = Simulator::new()?;
+    simulator.send(event_type)?;
+    simulator.flush()
+}
+
+/// Types `text` with the current layout. Characters missing from the layout
+/// get temporarily mapped on a spare keycode.
+///
+/// ```no_run
+/// use rdev::simulate_text;
+///
+/// simulate_text("Hello, wörld ✓").unwrap();
+/// ```
+pub fn simulate_text(text: &str) -> Result<(), SimulateError> {
+    let mut simulator = Simulator::new()?;
+    simulator.send_text(text)?;
+    simulator.flush()
+}
@@ -0,0 +1,295 @@
+use crate::linux::common::{convert_event, modifiers_from_state, EventFd};
+use crate::linux::xcb::Keyboard;
+use crate::rdev::{DeviceInfo, Event, EventType, KeyboardState, ListenError, Modifiers};
+use std::collections::HashMap;
+use std::io;
+use std::os::unix::io::AsRawFd;
+use std::path::PathBuf;
+use std::time::SystemTime;
+use x11rb::connection::{Connection, RequestConnection};
+use x11rb::protocol::xinput::{self, ConnectionExt as _, DeviceId, XIGetPropertyItems};
+use x11rb::protocol::xproto::{self, AtomEnum, ConnectionExt as _, Window};
+use x11rb::protocol::Event as XEvent;
+use x11rb::rust_connection::RustConnection;
+
+/// Listens to raw events through XInput2. Unlike RECORD, they say which
+/// device they come from, and keep coming while another client grabs the
+/// keyboard.
+pub struct RawListener {
+    conn: RustConnection,
+    root: Window,
+    keyboard: Keyboard,
+    /// Raw events carry no state, so it is tracked from the one at startup.
+    modifiers: Modifiers,
+    /// Devices by XInput id, forgotten when devices come and go.
+    devices: HashMap<DeviceId, Option<DeviceInfo>>,
+}
+
+impl RawListener {
+    pub fn new() -> Result<RawListener, ListenError> {
+        let keyboard = Keyboard::new().ok_or(ListenError::KeyboardError)?;
+        let (conn, screen) =
+            RustConnection::connect(None).map_err(|_| ListenError::MissingDisplayError)?;
+        let root = conn.setup().roots[screen].root;
+        let mut listener = RawListener {
+            conn,
+            root,
+            keyboard,
+            modifiers: Modifiers::empty(),
+            devices: HashMap::new(),
+        };
+
+        let extension = listener
+            .conn
+            .extension_information(xinput::X11_EXTENSION_NAME)
+            .map_err(|_| ListenError::XInputExtensionError)?;
+        if extension.is_none() {
+            return Err(ListenError::XInputExtensionError);
+        }
+        // Raw events reach the root window even during grabs since 2.1.
+        let version = listener
+            .conn
+            .xinput_xi_query_version(2, 1)
+            .map_err(|_| ListenError::XInputExtensionError)?
+            .reply()
+            .map_err(|_| ListenError::XInputExtensionError)?;
+        if (version.major_version, version.minor_version) < (2, 1) {
+            return Err(ListenError::XInputExtensionError);
+        }
+
+        let masks = [
+            xinput::EventMask {
+                deviceid: xinput::Device::ALL_MASTER.into(),
+                mask: vec![
+                    xinput::XIEventMask::RAW_KEY_PRESS
+                        | xinput::XIEventMask::RAW_KEY_RELEASE
+                        | xinput::XIEventMask::RAW_BUTTON_PRESS
+                        | xinput::XIEventMask::RAW_BUTTON_RELEASE
+                        | xinput::XIEventMask::RAW_MOTION,
+                ],
+            },
+            // Hierarchy changes can only be selected for all devices.
+            xinput::EventMask {
+                deviceid: xinput::Device::ALL.into(),
+                mask: vec![xinput::XIEventMask::HIERARCHY],
+            },
+        ];
+        listener
+            .conn
+            .xinput_xi_select_events(root, &masks)
+            .map_err(|_| ListenError::XInputExtensionError)?
+            .check()
+            .map_err(|_| ListenError::XInputExtensionError)?;
+
+        if let Some((_, _, state)) = listener.query_pointer(None) {
+            listener.modifiers = state;
+        }
+        Ok(listener)
+    }
+
+    /// Sends events until `stop` gets notified.
+    pub fn run<T>(&mut self, stop: &EventFd, mut send: T) -> Result<(), ListenError>
+    where
+        T: FnMut(Event),
+    {
+        let mut fds = [
+            libc::pollfd {
+                fd: self.conn.stream().as_raw_fd(),
+                events: libc::POLLIN,
+                revents: 0,
+            },
+            libc::pollfd {
+                fd: stop.as_raw_fd(),
+                events: libc::POLLIN,
+                revents: 0,
+            },
+        ];
+        loop {
+            // The connection might already hold queued events, handle them
+            // before waiting on the socket.
+            while let Some(event) = self
+                .conn
+                .poll_for_event()
+                .map_err(|_| ListenError::MissingDisplayError)?
+            {
+                if let Some(event) = self.handle(event) {
+                    send(event);
+                }
+            }
+            let ready = unsafe { libc::poll(fds.as_mut_ptr(), fds.len() as libc::nfds_t, -1) };
+            if ready < 0 {
+                let error = io::Error::last_os_error();
+                if error.kind() == io::ErrorKind::Interrupted {
+                    continue;
+                }
+                return Err(error.into());
+            }
+            if fds[1].revents != 0 {
+                return Ok(());
+            }
+            if fds[0].revents & (libc::POLLERR | libc::POLLHUP) != 0 {
+                return Err(ListenError::MissingDisplayError);
+            }
+        }
+    }
+
+    fn handle(&mut self, event: XEvent) -> Option<Event> {
+        // Key codes fit in a byte, as in the core protocol.
+        let (event_type, sourceid) = match event {
+            XEvent::XinputRawKeyPress(raw) => (
+                convert_event(raw.detail as u8, xproto::KEY_PRESS_EVENT.into(), 0.0, 0.0),
+                raw.sourceid,
+            ),
+            XEvent::XinputRawKeyRelease(raw) => (
+                convert_event(raw.detail as u8, xproto::KEY_RELEASE_EVENT.into(), 0.0, 0.0),
+                raw.sourceid,
+            ),
+            XEvent::XinputRawButtonPress(raw) => (
+                convert_event(
+                    raw.detail as u8,
+                    xproto::BUTTON_PRESS_EVENT.into(),
+                    0.0,
+                    0.0,
+                ),
+                raw.sourceid,
+            ),
+            XEvent::XinputRawButtonRelease(raw) => (
+                convert_event(
+                    raw.detail as u8,
+                    xproto::BUTTON_RELEASE_EVENT.into(),
+                    0.0,
+                    0.0,
+                ),
+                raw.sourceid,
+            ),
+            XEvent::XinputRawMotion(raw) => {
+                // Smooth scrolling moves other valuators, the wheel comes as
+                // emulated buttons 4 and 5 too.
+                if raw
+                    .valuator_mask
+                    .first()
+                    .map_or(true, |mask| mask & 0b11 == 0)
+                {
+                    return None;
+                }
+                let (x, y, _) = self.query_pointer(Some(raw.deviceid))?;
+                (Some(EventType::MouseMove { x, y }), raw.sourceid)
+            }
+            XEvent::XinputHierarchy(_) => {
+                self.devices.clear();
+                return None;
+            }
+            _ => return None,
+        };
+        let event_type = event_type?;
+        let name = self.keyboard.add(&event_type);
+        self.modifiers.update(&event_type);
+        Some(Event {
+            event_type,
+            time: SystemTime::now(),
+            name,
+            modifiers: self.modifiers,
+            device: self.device(sourceid),
+        })
+    }
+
+    /// Position of the `pointer` master device, or the client one, with the
+    /// modifiers.
+    fn query_pointer(&self, pointer: Option<DeviceId>) -> Option<(f64, f64, Modifiers)> {
+        let deviceid = match pointer {
+            Some(pointer) => pointer,
+            None => {
+                self.conn
+                    .xinput_xi_get_client_pointer(x11rb::NONE)
+                    .ok()?
+                    .reply()
+                    .ok()?
+                    .deviceid
+            }
+        };
+        let pointer = self
+            .conn
+            .xinput_xi_query_pointer(self.root, deviceid)
+            .ok()?
+            .reply()
+            .ok()?;
+        if !pointer.same_screen {
+            return None;
+        }
+        Some((
+            fixed_to_f64(pointer.root_x),
+            fixed_to_f64(pointer.root_y),
+            modifiers_from_state(pointer.mods.effective),
+        ))
+    }
+
+    fn device(&mut self, id: DeviceId) -> Option<DeviceInfo> {
+        if !self.devices.contains_key(&id) {
+            let info = self.query_device(id);
+            self.devices.insert(id, info);
+        }
+        self.devices[&id].clone()
+    }
+
+    /// Only devices backed by an evdev node have an id worth reporting, the
+    /// XTest ones don't.
+    fn query_device(&self, id: DeviceId) -> Option<DeviceInfo> {
+        let path = match self.property(id, b"Device Node")? {
+            XIGetPropertyItems::Data8(mut node) => {
+                while node.last() == Some(&0) {
+                    node.pop();
+                }
+                PathBuf::from(String::from_utf8(node).ok()?)
+            }
+            _ => return None,
+        };
+        let event_id = path
+            .file_name()
+            .and_then(|name| name.to_str()?.strip_prefix("event")?.parse().ok())?;
+        let info = self
+            .conn
+            .xinput_xi_query_device(id)
+            .ok()?
+            .reply()
+            .ok()?
+            .infos
+            .into_iter()
+            .next()?;
+        // CARD32 vendor and product.
+        let (vendor, product) = match self.property(id, b"Device Product ID") {
+            Some(XIGetPropertyItems::Data32(ids)) if ids.len() >= 2 => {
+                (ids[0] as u16, ids[1] as u16)
+            }
+            _ => (0, 0),
+        };
+        Some(DeviceInfo {
+            id: event_id,
+            path: Some(path),
+            name: String::from_utf8_lossy(&info.name).into_owned(),
+            phys: None,
+            vendor,
+            product,
+            keyboard: info.type_ == xinput::DeviceType::SLAVE_KEYBOARD,
+            pointer: info.type_ == xinput::DeviceType::SLAVE_POINTER,
+        })
+    }
+
+    fn property(&self, id: DeviceId, name: &[u8]) -> Option<XIGetPropertyItems> {
+        let atom = self.conn.intern_atom(true, name).ok()?.reply().ok()?.atom;
+        if atom == x11rb::NONE {
+            return None;
+        }
+        let property = self
+            .conn
+            .xinput_xi_get_property(id, false, atom, AtomEnum::ANY.into(), 0, 1024)
+            .ok()?
+            .reply()
+            .ok()?;
+        Some(property.items)
+    }
+}
+
+/// XInput2 sends coordinates as 16.16 fixed point numbers.
+fn fixed_to_f64(value: xinput::Fp1616) -> f64 {
+    f64::from(value) / 65536.0
+}
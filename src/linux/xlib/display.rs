@@ -0,0 +1,126 @@
+#[cfg(feature = "unstable_grab")]
+use crate::linux::common::modifiers_from_state;
+use crate::linux::xlib::FALSE;
+#[cfg(feature = "unstable_grab")]
+use crate::rdev::Modifiers;
+use std::convert::TryInto;
+use std::ffi::CStr;
+use std::os::raw::c_int;
+#[cfg(feature = "unstable_grab")]
+use std::os::raw::c_uint;
+use std::ptr::null;
+use x11::xinput2;
+use x11::xlib;
+use x11::xrecord;
+
+pub struct Display {
+    display: *mut xlib::Display,
+}
+
+impl Display {
+    pub fn new() -> Option<Display> {
+        unsafe {
+            let display = xlib::XOpenDisplay(null());
+            if display.is_null() {
+                return None;
+            }
+            Some(Display { display })
+        }
+    }
+
+    pub fn get_size(&self) -> Option<(u64, u64)> {
+        unsafe {
+            let screen_ptr = xlib::XDefaultScreenOfDisplay(self.display);
+            if screen_ptr.is_null() {
+                return None;
+            }
+            let screen = *screen_ptr;
+            Some((
+                screen.width.try_into().ok()?,
+                screen.height.try_into().ok()?,
+            ))
+        }
+    }
+
+    /// Whether the X server has XInput 2.1, which raw events need.
+    pub fn has_xinput2(&self) -> bool {
+        let (mut major, mut minor) = (2, 1);
+        self.has_extension(b"XInputExtension\0")
+            && unsafe { xinput2::XIQueryVersion(self.display, &mut major, &mut minor) }
+                == xlib::Success as c_int
+            && (major, minor) >= (2, 1)
+    }
+
+    pub fn has_record(&self) -> bool {
+        let (mut major, mut minor) = (1, 13);
+        self.has_extension(b"RECORD\0")
+            && unsafe { xrecord::XRecordQueryVersion(self.display, &mut major, &mut minor) } != 0
+    }
+
+    pub fn has_xtest(&self) -> bool {
+        self.has_extension(b"XTEST\0")
+    }
+
+    /// `name` ends with a nul byte.
+    fn has_extension(&self, name: &[u8]) -> bool {
+        let name = match CStr::from_bytes_with_nul(name) {
+            Ok(name) => name,
+            Err(_) => return false,
+        };
+        let (mut opcode, mut event, mut error) = (0, 0, 0);
+        unsafe {
+            xlib::XQueryExtension(
+                self.display,
+                name.as_ptr(),
+                &mut opcode,
+                &mut event,
+                &mut error,
+            ) != FALSE
+        }
+    }
+
+    #[cfg(feature = "unstable_grab")]
+    pub fn get_mouse_pos(&self) -> Option<(u64, u64)> {
+        let (root_x, root_y, _) = self.query_pointer()?;
+        Some((root_x.try_into().ok()?, root_y.try_into().ok()?))
+    }
+
+    #[cfg(feature = "unstable_grab")]
+    pub fn get_modifiers(&self) -> Option<Modifiers> {
+        let (_, _, mask) = self.query_pointer()?;
+        Some(modifiers_from_state(mask))
+    }
+
+    #[cfg(feature = "unstable_grab")]
+    fn query_pointer(&self) -> Option<(c_int, c_int, c_uint)> {
+        unsafe {
+            let root_window = xlib::XRootWindow(self.display, 0);
+            let mut root_x = 0;
+            let mut root_y = 0;
+            let mut x = 0;
+            let mut y = 0;
+            let mut root = 0;
+            let mut child = 0;
+            let mut mask = 0;
+            let _screen_ptr = xlib::XQueryPointer(
+                self.display,
+                root_window,
+                &mut root,
+                &mut child,
+                &mut root_x,
+                &mut root_y,
+                &mut x,
+                &mut y,
+                &mut mask,
+            );
+            Some((root_x, root_y, mask))
+        }
+    }
+}
+impl Drop for Display {
+    fn drop(&mut self) {
+        unsafe {
+            xlib::XCloseDisplay(self.display);
+        }
+    }
+}
@@ -1,5 +1,7 @@
+extern crate libc;
 extern crate x11;
 use crate::linux::keycodes::code_from_key;
+use crate::linux::xlib::TRUE;
 use crate::rdev::{EventType, Key, KeyboardState};
 use std::ffi::CString;
 use std::os::raw::{c_char, c_int, c_uint, c_ulong, c_void};
@@ -9,6 +11,7 @@ use x11::xlib;
 #[derive(Debug)]
 struct State {
     alt: bool,
+    alt_gr: bool,
     ctrl: bool,
     caps_lock: bool,
     shift: bool,
@@ -24,6 +27,7 @@ impl State {
     fn new() -> State {
         State {
             alt: false,
+            alt_gr: false,
             ctrl: false,
             caps_lock: false,
             meta: false,
@@ -31,13 +35,17 @@ impl State {
         }
     }
 
+    /// The mask keys are looked up with. Control is left out, since it would
+    /// turn letters into control characters while the name stays the letter.
     fn value(&self) -> c_uint {
         let mut res: c_uint = 0;
         if self.alt {
             res += xlib::Mod1Mask;
         }
-        if self.ctrl {
-            res += xlib::ControlMask;
+        // ISO_Level3_Shift is bound to Mod5 by the usual XKB configurations,
+        // it selects the third and fourth levels of the key.
+        if self.alt_gr {
+            res += xlib::Mod5Mask;
         }
         if self.caps_lock {
             res += xlib::LockMask;
@@ -50,6 +58,20 @@ impl State {
         }
         res
     }
+
+    /// Updates the modifier `key` stands for, returns false if it is not one.
+    fn set_modifier(&mut self, key: Key, pressed: bool) -> bool {
+        let modifier = match key {
+            Key::Alt => &mut self.alt,
+            Key::AltGr => &mut self.alt_gr,
+            Key::ControlLeft | Key::ControlRight => &mut self.ctrl,
+            Key::MetaLeft | Key::MetaRight => &mut self.meta,
+            Key::ShiftLeft | Key::ShiftRight => &mut self.shift,
+            _ => return false,
+        };
+        *modifier = pressed;
+        true
+    }
 }
 
 #[derive(Debug)]
@@ -75,6 +97,8 @@ impl Keyboard {
     pub fn new() -> Option<Keyboard> {
         unsafe {
             // https://stackoverflow.com/questions/18246848/get-utf-8-input-with-x11-display#
+            // The built-in input method still handles dead keys and Compose
+            // sequences with the system Compose table.
             let string = CString::new("@im=none").expect("Can't creat CString");
             let ret = xlib::XSetLocaleModifiers(string.as_ptr());
             NonNull::new(ret)?;
@@ -155,7 +179,7 @@ impl Keyboard {
             println!("We don't seem to have a display or a xic");
             return None;
         }
-        const BUF_LEN: usize = 4;
+        const BUF_LEN: usize = 32;
         let mut buf = [0_u8; BUF_LEN];
         let key = xlib::XKeyEvent {
             display: *self.display,
@@ -183,7 +207,20 @@ impl Keyboard {
         // the correct keyvalue from the utf8LookupString !!
         // https://stackoverflow.com/questions/18246848/get-utf-8-input-with-x11-display#
         // -----------------------------------------------------------------
-        xlib::XFilterEvent(&mut event, 0);
+        if xlib::XFilterEvent(&mut event, 0) == TRUE {
+            // The key belongs to a dead key or Compose sequence. Once the
+            // sequence is complete, the input method puts back a key press
+            // with keycode 0 carrying the composed string.
+            let composed = xlib::XCheckTypedWindowEvent(
+                *self.display,
+                *self.window,
+                xlib::KeyPress,
+                &mut event,
+            );
+            if composed != TRUE {
+                return None;
+            }
+        }
 
         let ret = xlib::Xutf8LookupString(
             *self.xic,
@@ -206,32 +243,33 @@ impl KeyboardState for Keyboard {
     fn add(&mut self, event_type: &EventType) -> Option<String> {
         match event_type {
             EventType::KeyPress(key) => match key {
-                Key::ShiftLeft | Key::ShiftRight => {
-                    self.state.shift = true;
-                    None
-                }
                 Key::CapsLock => {
                     self.state.caps_lock = !self.state.caps_lock;
                     None
                 }
+                key if self.state.set_modifier(*key, true) => None,
                 key => {
                     let keycode = code_from_key(*key)?;
                     let state = self.state.value();
                     unsafe { self.name_from_code(keycode, state) }
                 }
             },
-            EventType::KeyRelease(key) => match key {
-                Key::ShiftLeft | Key::ShiftRight => {
-                    self.state.shift = false;
-                    None
-                }
-                _ => None,
-            },
+            EventType::KeyRelease(key) => {
+                self.state.set_modifier(*key, false);
+                None
+            }
             _ => None,
         }
     }
     fn reset(&mut self) {
         self.state = State::new();
+        // Drops any pending dead key or Compose sequence.
+        unsafe {
+            let pending = xlib::Xutf8ResetIC(*self.xic);
+            if !pending.is_null() {
+                xlib::XFree(pending as *mut c_void);
+            }
+        }
     }
 }
 
@@ -239,12 +277,31 @@ impl KeyboardState for Keyboard {
 mod tests {
     use super::*;
 
+    #[test]
+    fn test_modifier_state() {
+        let mut state = State::new();
+        assert!(state.set_modifier(Key::AltGr, true));
+        assert!(state.set_modifier(Key::ShiftRight, true));
+        assert!(!state.set_modifier(Key::KeyQ, true));
+        assert_eq!(state.value(), xlib::Mod5Mask | xlib::ShiftMask);
+        state.set_modifier(Key::ShiftRight, false);
+        assert!(state.set_modifier(Key::ControlLeft, true));
+        assert!(state.ctrl);
+        state.set_modifier(Key::MetaRight, true);
+        assert_eq!(state.value(), xlib::Mod5Mask | xlib::Mod4Mask);
+        state.set_modifier(Key::AltGr, false);
+        state.set_modifier(Key::ControlLeft, false);
+        state.set_modifier(Key::MetaRight, false);
+        assert!(!state.ctrl);
+        assert_eq!(state.value(), 0);
+    }
+
     #[test]
     #[ignore]
     /// If the following tests run, they *will* cause a crash because xlib
     /// is *not* thread safe. Ignoring the tests for now.
-    /// XCB *could* be an option but not even sure we can get dead keys again.
-    /// XCB doc is sparse on the web let's say.
+    /// The x11rb backend's keyboard, built on xkbcommon, doesn't have this
+    /// problem.
     fn test_thread_safety() {
         let mut keyboard = Keyboard::new().unwrap();
         let char_s = keyboard.add(&EventType::KeyPress(Key::KeyS)).unwrap();
@@ -266,4 +323,40 @@ mod tests {
             "This test should pass only on Qwerty layout !"
         );
     }
+
+    #[test]
+    #[ignore]
+    /// Needs an X server with the "us intl" layout, where AltGr types the
+    /// third and fourth levels.
+    fn test_levels() {
+        let mut keyboard = Keyboard::new().unwrap();
+        keyboard.add(&EventType::KeyPress(Key::AltGr));
+        let level3 = keyboard.add(&EventType::KeyPress(Key::KeyQ));
+        keyboard.add(&EventType::KeyPress(Key::ShiftLeft));
+        let level4 = keyboard.add(&EventType::KeyPress(Key::KeyQ));
+        keyboard.add(&EventType::KeyRelease(Key::ShiftLeft));
+        keyboard.add(&EventType::KeyRelease(Key::AltGr));
+        assert_eq!(level3, Some("ä".to_string()));
+        assert_eq!(level4, Some("Ä".to_string()));
+
+        keyboard.add(&EventType::KeyPress(Key::ControlLeft));
+        let ctrl_a = keyboard.add(&EventType::KeyPress(Key::KeyA));
+        assert_eq!(ctrl_a, Some("a".to_string()));
+    }
+
+    #[test]
+    #[ignore]
+    /// Needs an X server with the "us intl" layout, where ' is a dead key,
+    /// and a locale set like programs have to.
+    fn test_dead_keys() {
+        unsafe {
+            libc::setlocale(libc::LC_CTYPE, b"\0".as_ptr() as *const c_char);
+        }
+        let mut keyboard = Keyboard::new().unwrap();
+        assert_eq!(keyboard.add(&EventType::KeyPress(Key::Quote)), None);
+        assert_eq!(
+            keyboard.add(&EventType::KeyPress(Key::KeyE)),
+            Some("é".to_string())
+        );
+    }
 }
@@ -0,0 +1,16 @@
+mod display;
+mod keyboard;
+mod record;
+mod simulate;
+mod xinput;
+
+use std::os::raw::c_int;
+
+pub use crate::linux::xlib::display::Display;
+pub use crate::linux::xlib::keyboard::Keyboard;
+pub use crate::linux::xlib::record::Recorder;
+pub use crate::linux::xlib::simulate::{simulate, simulate_text, Simulator};
+pub use crate::linux::xlib::xinput::RawListener;
+
+pub const TRUE: c_int = 1;
+pub const FALSE: c_int = 0;
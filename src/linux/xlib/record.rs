@@ -0,0 +1,203 @@
+extern crate libc;
+extern crate x11;
+use crate::linux::common::{convert, EventFd};
+use crate::linux::listen::broadcast;
+use crate::linux::xlib::{Keyboard, FALSE};
+use crate::rdev::ListenError;
+use std::convert::TryInto;
+use std::ffi::CStr;
+use std::io;
+use std::os::raw::{c_char, c_int, c_uchar, c_uint, c_void};
+use std::os::unix::io::AsRawFd;
+use std::ptr::null;
+use x11::xlib;
+use x11::xrecord;
+
+/// What the record callback needs, handed over through the context closure.
+struct RecordState {
+    keyboard: Keyboard,
+    generation: u64,
+}
+
+/// Owns the XRecord context and the two X connections it needs: the control
+/// one creates and disables the context, the data one receives the
+/// intercepted events.
+pub struct Recorder {
+    dpy_control: *mut xlib::Display,
+    dpy_data: *mut xlib::Display,
+    context: xrecord::XRecordContext,
+    state: Box<RecordState>,
+}
+
+impl Recorder {
+    pub fn new(generation: u64) -> Result<Recorder, ListenError> {
+        let keyboard = Keyboard::new().ok_or(ListenError::KeyboardError)?;
+
+        unsafe {
+            // Open displays
+            let dpy_control = xlib::XOpenDisplay(null());
+            if dpy_control.is_null() {
+                return Err(ListenError::MissingDisplayError);
+            }
+            let dpy_data = xlib::XOpenDisplay(null());
+            if dpy_data.is_null() {
+                xlib::XCloseDisplay(dpy_control);
+                return Err(ListenError::MissingDisplayError);
+            }
+            // From now on, dropping the recorder closes the displays.
+            let mut recorder = Recorder {
+                dpy_control,
+                dpy_data,
+                context: 0,
+                state: Box::new(RecordState {
+                    keyboard,
+                    generation,
+                }),
+            };
+
+            let extension_name = CStr::from_bytes_with_nul(b"RECORD\0")
+                .map_err(|_| ListenError::XRecordExtensionError)?;
+            let extension = xlib::XInitExtension(dpy_control, extension_name.as_ptr());
+            if extension.is_null() {
+                return Err(ListenError::XRecordExtensionError);
+            }
+
+            // Prepare record range
+            let mut record_range = xrecord::XRecordAllocRange();
+            if record_range.is_null() {
+                return Err(ListenError::RecordContextError);
+            }
+            (*record_range).device_events.first = xlib::KeyPress as c_uchar;
+            (*record_range).device_events.last = xlib::MotionNotify as c_uchar;
+
+            // Create context
+            let mut record_all_clients = xrecord::XRecordAllClients;
+            recorder.context = xrecord::XRecordCreateContext(
+                dpy_control,
+                0,
+                &mut record_all_clients,
+                1,
+                &mut record_range,
+                1,
+            );
+            xlib::XFree(record_range as *mut c_void);
+
+            if recorder.context == 0 {
+                return Err(ListenError::RecordContextError);
+            }
+
+            xlib::XSync(dpy_control, FALSE);
+            // The async variant lets us wait on the connection ourselves, so
+            // that the loop can be woken up to stop.
+            let state: *mut RecordState = &mut *recorder.state;
+            let result = xrecord::XRecordEnableContextAsync(
+                dpy_data,
+                recorder.context,
+                Some(record_callback),
+                state as *mut c_char,
+            );
+            if result == 0 {
+                return Err(ListenError::RecordContextEnablingError);
+            }
+            Ok(recorder)
+        }
+    }
+
+    /// Dispatches events until `stop` gets notified.
+    pub fn run(&self, stop: &EventFd) -> Result<(), ListenError> {
+        let mut fds = [
+            libc::pollfd {
+                fd: unsafe { xlib::XConnectionNumber(self.dpy_data) },
+                events: libc::POLLIN,
+                revents: 0,
+            },
+            libc::pollfd {
+                fd: stop.as_raw_fd(),
+                events: libc::POLLIN,
+                revents: 0,
+            },
+        ];
+        loop {
+            // Xlib might already hold buffered replies, dispatch them before
+            // waiting on the socket.
+            unsafe { xrecord::XRecordProcessReplies(self.dpy_data) };
+            let ready = unsafe { libc::poll(fds.as_mut_ptr(), fds.len() as libc::nfds_t, -1) };
+            if ready < 0 {
+                let error = io::Error::last_os_error();
+                if error.kind() == io::ErrorKind::Interrupted {
+                    continue;
+                }
+                return Err(error.into());
+            }
+            if fds[1].revents != 0 {
+                return Ok(());
+            }
+            if fds[0].revents & (libc::POLLERR | libc::POLLHUP) != 0 {
+                return Err(ListenError::MissingDisplayError);
+            }
+        }
+    }
+}
+
+impl Drop for Recorder {
+    fn drop(&mut self) {
+        unsafe {
+            if self.context != 0 {
+                xrecord::XRecordDisableContext(self.dpy_control, self.context);
+                xrecord::XRecordFreeContext(self.dpy_control, self.context);
+                xlib::XSync(self.dpy_control, FALSE);
+            }
+            xlib::XCloseDisplay(self.dpy_data);
+            xlib::XCloseDisplay(self.dpy_control);
+        }
+    }
+}
+
+// No idea how to do that properly relevant doc lives here:
+// https://www.x.org/releases/X11R7.7/doc/libXtst/recordlib.html#Datum_Flags
+// https://docs.rs/xproto/1.1.5/xproto/struct._xEvent__bindgen_ty_1.html
+// 0.4.2: xproto was removed for some reason and contained the real structs
+// but we can't use it anymore.
+#[repr(C)]
+struct XRecordDatum {
+    type_: u8,
+    code: u8,
+    _rest: u64,
+    _1: bool,
+    _2: bool,
+    _3: bool,
+    root_x: i16,
+    root_y: i16,
+    event_x: i16,
+    event_y: i16,
+    state: u16,
+}
+
+unsafe extern "C" fn record_callback(
+    closure: *mut c_char,
+    raw_data: *mut xrecord::XRecordInterceptData,
+) {
+    let data = raw_data.as_ref().unwrap();
+    if data.category != xrecord::XRecordFromServer {
+        xrecord::XRecordFreeData(raw_data);
+        return;
+    }
+
+    debug_assert!(data.data_len * 4 >= std::mem::size_of::<XRecordDatum>().try_into().unwrap());
+    // Cast binary data
+    #[allow(clippy::cast_ptr_alignment)]
+    let xdatum = (data.data as *const XRecordDatum).as_ref().unwrap();
+
+    let code: c_uint = xdatum.code.into();
+    let type_: c_int = xdatum.type_.into();
+
+    let x = xdatum.root_x as f64;
+    let y = xdatum.root_y as f64;
+    let modifiers: c_uint = xdatum.state.into();
+
+    let state = &mut *(closure as *mut RecordState);
+    if let Some(event) = convert(&mut state.keyboard, code, type_, x, y, modifiers) {
+        broadcast(state.generation, event);
+    }
+    xrecord::XRecordFreeData(raw_data);
+}
@@ -0,0 +1,296 @@
+use crate::linux::keycodes::code_from_key;
+use crate::linux::keymap::{keysym_from_char, Keymap, NO_SYMBOL, REMAP_DELAY};
+use crate::linux::xlib::{FALSE, TRUE};
+use crate::rdev::{Button, EventType, SimulateError};
+use std::convert::TryInto;
+use std::os::raw::{c_int, c_uint, c_void};
+use std::ptr::null;
+use std::slice;
+use std::thread;
+use x11::keysym;
+use x11::xlib;
+use x11::xtest;
+
+unsafe fn send_native(event_type: &EventType, display: *mut xlib::Display) -> Option<()> {
+    let res = match event_type {
+        EventType::KeyPress(key) => {
+            let code = code_from_key(*key)?;
+            xtest::XTestFakeKeyEvent(display, code, TRUE, 0)
+        }
+        EventType::KeyRelease(key) => {
+            let code = code_from_key(*key)?;
+            xtest::XTestFakeKeyEvent(display, code, FALSE, 0)
+        }
+        EventType::ButtonPress(button) => match button {
+            Button::Left => xtest::XTestFakeButtonEvent(display, 1, TRUE, 0),
+            Button::Middle => xtest::XTestFakeButtonEvent(display, 2, TRUE, 0),
+            Button::Right => xtest::XTestFakeButtonEvent(display, 3, TRUE, 0),
+            Button::Unknown(code) => xtest::XTestFakeButtonEvent(display, (*code).into(), TRUE, 0),
+        },
+        EventType::ButtonRelease(button) => match button {
+            Button::Left => xtest::XTestFakeButtonEvent(display, 1, FALSE, 0),
+            Button::Middle => xtest::XTestFakeButtonEvent(display, 2, FALSE, 0),
+            Button::Right => xtest::XTestFakeButtonEvent(display, 3, FALSE, 0),
+            Button::Unknown(code) => xtest::XTestFakeButtonEvent(display, (*code).into(), FALSE, 0),
+        },
+        EventType::MouseMove { x, y } => {
+            //TODO: replace with clamp if it is stabalized
+            let x = if x.is_finite() {
+                x.min(c_int::MAX.into()).max(c_int::MIN.into()).round() as c_int
+            } else {
+                0
+            };
+            let y = if y.is_finite() {
+                y.min(c_int::MAX.into()).max(c_int::MIN.into()).round() as c_int
+            } else {
+                0
+            };
+            xtest::XTestFakeMotionEvent(display, 0, x, y, 0)
+            //     xlib::XWarpPointer(display, 0, root, 0, 0, 0, 0, *x as i32, *y as i32);
+        }
+        EventType::Wheel { delta_y, .. } => {
+            let code = if *delta_y > 0 { 4 } else { 5 };
+            xtest::XTestFakeButtonEvent(display, code, TRUE, 0)
+                & xtest::XTestFakeButtonEvent(display, code, FALSE, 0)
+        }
+    };
+    if res == 0 {
+        None
+    } else {
+        Some(())
+    }
+}
+
+/// Keeps a single X connection open to send many events through XTest.
+pub struct Simulator {
+    display: *mut xlib::Display,
+}
+
+impl Simulator {
+    pub fn new() -> Result<Simulator, SimulateError> {
+        let display = unsafe { xlib::XOpenDisplay(null()) };
+        if display.is_null() {
+            return Err(SimulateError);
+        }
+        Ok(Simulator { display })
+    }
+
+    /// Sends the event out without waiting for the X server to process it.
+    pub fn send(&mut self, event_type: &EventType) -> Result<(), SimulateError> {
+        unsafe {
+            send_native(event_type, self.display).ok_or(SimulateError)?;
+            xlib::XFlush(self.display);
+        }
+        Ok(())
+    }
+
+    /// Moves the pointer by `dx` and `dy` pixels.
+    pub fn move_by(&mut self, dx: i32, dy: i32) -> Result<(), SimulateError> {
+        unsafe {
+            if xtest::XTestFakeRelativeMotionEvent(self.display, 0, dx, dy, 0) == 0 {
+                return Err(SimulateError);
+            }
+            xlib::XFlush(self.display);
+        }
+        Ok(())
+    }
+
+    /// Sends all events in order, with a single round of writes to the X server.
+    /// Stops at the first event that can't be sent.
+    pub fn send_batch(&mut self, event_types: &[EventType]) -> Result<(), SimulateError> {
+        let result = event_types
+            .iter()
+            .try_for_each(|event_type| unsafe { send_native(event_type, self.display) });
+        unsafe {
+            xlib::XFlush(self.display);
+        }
+        result.ok_or(SimulateError)
+    }
+
+    /// Types `text` with the current layout. Characters that no key can type
+    /// get temporarily mapped on a spare keycode, which is restored shortly
+    /// after the whole text got typed. CapsLock gets turned off while typing.
+    pub fn send_text(&mut self, text: &str) -> Result<(), SimulateError> {
+        unsafe {
+            let mut keymap = get_keymap(self.display).ok_or(SimulateError)?;
+            let shift = keymap
+                .keycode(keysym::XK_Shift_L.into())
+                .ok_or(SimulateError)?;
+            let level3 = keymap.keycode(keysym::XK_ISO_Level3_Shift.into());
+            let spares = keymap.spare_keycodes();
+            let mut remapped = Vec::new();
+            let caps_lock = if caps_locked(self.display) {
+                keymap.keycode(keysym::XK_Caps_Lock.into())
+            } else {
+                None
+            };
+            if let Some(caps_lock) = caps_lock {
+                type_key(self.display, caps_lock, &[]).ok_or(SimulateError)?;
+            }
+
+            let result = text.chars().try_for_each(|ch| {
+                let keysym = keysym_from_char(ch);
+                let (keycode, level) = match keymap.find(keysym, level3.is_some()) {
+                    Some(found) => found,
+                    None => {
+                        // Once every spare keycode is in use, wait for the
+                        // characters typed so far before giving them back.
+                        if remapped.len() == spares.len() {
+                            restore(self.display, &mut keymap, &mut remapped);
+                        }
+                        let keycode = *spares.get(remapped.len())?;
+                        remap(self.display, &mut keymap, keycode, keysym);
+                        remapped.push(keycode);
+                        (keycode, 0)
+                    }
+                };
+                let mut modifiers = Vec::new();
+                if level >= 2 {
+                    modifiers.push(level3?);
+                }
+                if level % 2 == 1 {
+                    modifiers.push(shift);
+                }
+                type_key(self.display, keycode, &modifiers)
+            });
+
+            if let Some(caps_lock) = caps_lock {
+                type_key(self.display, caps_lock, &[]).ok_or(SimulateError)?;
+            }
+            restore(self.display, &mut keymap, &mut remapped);
+            xlib::XFlush(self.display);
+            result.ok_or(SimulateError)
+        }
+    }
+
+    /// Waits until the X server has processed every event sent so far.
+    pub fn flush(&mut self) -> Result<(), SimulateError> {
+        unsafe {
+            xlib::XSync(self.display, FALSE);
+        }
+        Ok(())
+    }
+}
+
+impl Drop for Simulator {
+    fn drop(&mut self) {
+        unsafe {
+            xlib::XCloseDisplay(self.display);
+        }
+    }
+}
+
+pub fn simulate(event_type: &EventType) -> Result<(), SimulateError> {
+    let mut simulator = Simulator::new()?;
+    simulator.send(event_type)?;
+    simulator.flush()
+}
+
+/// Types `text` with the current layout. Characters missing from the layout
+/// get temporarily mapped on a spare keycode.
+///
+/// ```no_run
+/// use rdev::simulate_text;
+///
+/// simulate_text("Hello, wörld ✓").unwrap();
+/// ```
+pub fn simulate_text(text: &str) -> Result<(), SimulateError> {
+    let mut simulator = Simulator::new()?;
+    simulator.send_text(text)?;
+    simulator.flush()
+}
+
+/// Presses `modifiers`, types `keycode`, then releases `modifiers`.
+unsafe fn type_key(
+    display: *mut xlib::Display,
+    keycode: c_uint,
+    modifiers: &[c_uint],
+) -> Option<()> {
+    let mut res = 1;
+    for modifier in modifiers {
+        res &= xtest::XTestFakeKeyEvent(display, *modifier, TRUE, 0);
+    }
+    res &= xtest::XTestFakeKeyEvent(display, keycode, TRUE, 0);
+    res &= xtest::XTestFakeKeyEvent(display, keycode, FALSE, 0);
+    for modifier in modifiers.iter().rev() {
+        res &= xtest::XTestFakeKeyEvent(display, *modifier, FALSE, 0);
+    }
+    if res == 0 {
+        None
+    } else {
+        Some(())
+    }
+}
+
+/// Whether CapsLock is on.
+unsafe fn caps_locked(display: *mut xlib::Display) -> bool {
+    let (mut root, mut child) = (0, 0);
+    let (mut root_x, mut root_y, mut x, mut y) = (0, 0, 0, 0);
+    let mut mask = 0;
+    xlib::XQueryPointer(
+        display,
+        xlib::XDefaultRootWindow(display),
+        &mut root,
+        &mut child,
+        &mut root_x,
+        &mut root_y,
+        &mut x,
+        &mut y,
+        &mut mask,
+    ) != FALSE
+        && mask & xlib::LockMask != 0
+}
+
+/// Gives the `remapped` spare keycodes back, once the X server got the keys
+/// typed with them and clients had the time to look them up.
+unsafe fn restore(display: *mut xlib::Display, keymap: &mut Keymap, remapped: &mut Vec<c_uint>) {
+    if remapped.is_empty() {
+        return;
+    }
+    xlib::XSync(display, FALSE);
+    thread::sleep(REMAP_DELAY);
+    for keycode in remapped.drain(..) {
+        remap(display, keymap, keycode, NO_SYMBOL);
+    }
+}
+
+unsafe fn get_keymap(display: *mut xlib::Display) -> Option<Keymap> {
+    let mut min_keycode = 0;
+    let mut max_keycode = 0;
+    xlib::XDisplayKeycodes(display, &mut min_keycode, &mut max_keycode);
+    let count = max_keycode - min_keycode + 1;
+    let mut keysyms_per_keycode = 0;
+    let mapping = xlib::XGetKeyboardMapping(
+        display,
+        min_keycode.try_into().ok()?,
+        count,
+        &mut keysyms_per_keycode,
+    );
+    if mapping.is_null() {
+        return None;
+    }
+    let len = (count * keysyms_per_keycode).try_into().ok()?;
+    let keysyms = slice::from_raw_parts(mapping, len).to_vec();
+    xlib::XFree(mapping as *mut c_void);
+    Some(Keymap::new(
+        min_keycode,
+        keysyms_per_keycode.try_into().ok()?,
+        keysyms,
+    ))
+}
+
+unsafe fn remap(
+    display: *mut xlib::Display,
+    keymap: &mut Keymap,
+    keycode: c_uint,
+    keysym: xlib::KeySym,
+) {
+    let keysyms = keymap.remap(keycode, keysym);
+    xlib::XChangeKeyboardMapping(
+        display,
+        keycode as c_int,
+        keysyms.len() as c_int,
+        keysyms.as_ptr() as *mut xlib::KeySym,
+        1,
+    );
+}
@@ -0,0 +1,381 @@
+extern crate libc;
+extern crate x11;
+use crate::linux::common::{convert_event, modifiers_from_state, EventFd};
+use crate::linux::xlib::{Keyboard, FALSE, TRUE};
+use crate::rdev::{DeviceInfo, Event, EventType, KeyboardState, ListenError, Modifiers};
+use std::collections::HashMap;
+use std::convert::TryInto;
+use std::ffi::CStr;
+use std::io;
+use std::mem::MaybeUninit;
+use std::os::raw::{c_int, c_uchar, c_ulong, c_void};
+use std::os::unix::io::AsRawFd;
+use std::path::PathBuf;
+use std::ptr::{null, null_mut};
+use std::slice;
+use std::time::SystemTime;
+use x11::xinput2;
+use x11::xlib;
+
+/// Listens to raw events through XInput2. Unlike RECORD, they say which
+/// device they come from, and keep coming while another client grabs the
+/// keyboard.
+pub struct RawListener {
+    display: *mut xlib::Display,
+    /// Major opcode of the extension, tagging its generic events.
+    opcode: c_int,
+    keyboard: Keyboard,
+    /// Raw events carry no state, so it is tracked from the one at startup.
+    modifiers: Modifiers,
+    /// Devices by XInput id, forgotten when devices come and go.
+    devices: HashMap<c_int, Option<DeviceInfo>>,
+}
+
+impl RawListener {
+    pub fn new() -> Result<RawListener, ListenError> {
+        let keyboard = Keyboard::new().ok_or(ListenError::KeyboardError)?;
+
+        unsafe {
+            let display = xlib::XOpenDisplay(null());
+            if display.is_null() {
+                return Err(ListenError::MissingDisplayError);
+            }
+            // From now on, dropping the listener closes the display.
+            let mut listener = RawListener {
+                display,
+                opcode: 0,
+                keyboard,
+                modifiers: Modifiers::empty(),
+                devices: HashMap::new(),
+            };
+
+            let extension_name = CStr::from_bytes_with_nul(b"XInputExtension\0")
+                .map_err(|_| ListenError::XInputExtensionError)?;
+            let mut event = 0;
+            let mut error = 0;
+            if xlib::XQueryExtension(
+                display,
+                extension_name.as_ptr(),
+                &mut listener.opcode,
+                &mut event,
+                &mut error,
+            ) == FALSE
+            {
+                return Err(ListenError::XInputExtensionError);
+            }
+            // Raw events reach the root window even during grabs since 2.1.
+            let mut major = 2;
+            let mut minor = 1;
+            if xinput2::XIQueryVersion(display, &mut major, &mut minor) != xlib::Success as c_int
+                || (major, minor) < (2, 1)
+            {
+                return Err(ListenError::XInputExtensionError);
+            }
+
+            let mut raw_mask = event_mask(&[
+                xinput2::XI_RawKeyPress,
+                xinput2::XI_RawKeyRelease,
+                xinput2::XI_RawButtonPress,
+                xinput2::XI_RawButtonRelease,
+                xinput2::XI_RawMotion,
+            ]);
+            // Hierarchy changes can only be selected for all devices.
+            let mut hierarchy_mask = event_mask(&[xinput2::XI_HierarchyChanged]);
+            let mut masks = [
+                xinput2::XIEventMask {
+                    deviceid: xinput2::XIAllMasterDevices,
+                    mask_len: raw_mask.len() as c_int,
+                    mask: raw_mask.as_mut_ptr(),
+                },
+                xinput2::XIEventMask {
+                    deviceid: xinput2::XIAllDevices,
+                    mask_len: hierarchy_mask.len() as c_int,
+                    mask: hierarchy_mask.as_mut_ptr(),
+                },
+            ];
+            let root = xlib::XDefaultRootWindow(display);
+            xinput2::XISelectEvents(display, root, masks.as_mut_ptr(), masks.len() as c_int);
+            xlib::XSync(display, FALSE);
+
+            if let Some((_, _, state)) = listener.query_pointer(None) {
+                listener.modifiers = state;
+            }
+            Ok(listener)
+        }
+    }
+
+    /// Sends events until `stop` gets notified.
+    pub fn run<T>(&mut self, stop: &EventFd, mut send: T) -> Result<(), ListenError>
+    where
+        T: FnMut(Event),
+    {
+        let mut fds = [
+            libc::pollfd {
+                fd: unsafe { xlib::XConnectionNumber(self.display) },
+                events: libc::POLLIN,
+                revents: 0,
+            },
+            libc::pollfd {
+                fd: stop.as_raw_fd(),
+                events: libc::POLLIN,
+                revents: 0,
+            },
+        ];
+        loop {
+            // Xlib might already hold buffered events, handle them before
+            // waiting on the socket.
+            while unsafe { xlib::XPending(self.display) } > 0 {
+                let mut event = MaybeUninit::<xlib::XEvent>::uninit();
+                let event = unsafe {
+                    xlib::XNextEvent(self.display, event.as_mut_ptr());
+                    event.assume_init()
+                };
+                if let Some(event) = self.handle(event) {
+                    send(event);
+                }
+            }
+            let ready = unsafe { libc::poll(fds.as_mut_ptr(), fds.len() as libc::nfds_t, -1) };
+            if ready < 0 {
+                let error = io::Error::last_os_error();
+                if error.kind() == io::ErrorKind::Interrupted {
+                    continue;
+                }
+                return Err(error.into());
+            }
+            if fds[1].revents != 0 {
+                return Ok(());
+            }
+            if fds[0].revents & (libc::POLLERR | libc::POLLHUP) != 0 {
+                return Err(ListenError::MissingDisplayError);
+            }
+        }
+    }
+
+    fn handle(&mut self, event: xlib::XEvent) -> Option<Event> {
+        let mut cookie = unsafe { event.generic_event_cookie };
+        if cookie.type_ != xlib::GenericEvent || cookie.extension != self.opcode {
+            return None;
+        }
+        unsafe {
+            if xlib::XGetEventData(self.display, &mut cookie) == FALSE {
+                return None;
+            }
+            let event = if cookie.evtype == xinput2::XI_HierarchyChanged {
+                self.devices.clear();
+                None
+            } else {
+                self.convert(&*(cookie.data as *const xinput2::XIRawEvent))
+            };
+            xlib::XFreeEventData(self.display, &mut cookie);
+            event
+        }
+    }
+
+    fn convert(&mut self, raw: &xinput2::XIRawEvent) -> Option<Event> {
+        // Key codes fit in a byte, as in the core protocol.
+        let detail = raw.detail as c_uchar;
+        let event_type = match raw.evtype {
+            xinput2::XI_RawKeyPress => convert_event(detail, xlib::KeyPress, 0.0, 0.0),
+            xinput2::XI_RawKeyRelease => convert_event(detail, xlib::KeyRelease, 0.0, 0.0),
+            xinput2::XI_RawButtonPress => convert_event(detail, xlib::ButtonPress, 0.0, 0.0),
+            xinput2::XI_RawButtonRelease => convert_event(detail, xlib::ButtonRelease, 0.0, 0.0),
+            xinput2::XI_RawMotion => {
+                // Smooth scrolling moves other valuators, the wheel comes as
+                // emulated buttons 4 and 5 too.
+                let valuators = raw.valuators;
+                let mask =
+                    unsafe { slice::from_raw_parts(valuators.mask, valuators.mask_len as usize) };
+                if !mask_is_set(mask, 0) && !mask_is_set(mask, 1) {
+                    return None;
+                }
+                let (x, y, _) = self.query_pointer(Some(raw.deviceid))?;
+                Some(EventType::MouseMove { x, y })
+            }
+            _ => None,
+        }?;
+        let name = self.keyboard.add(&event_type);
+        self.modifiers.update(&event_type);
+        Some(Event {
+            event_type,
+            time: SystemTime::now(),
+            name,
+            modifiers: self.modifiers,
+            device: self.device(raw.sourceid),
+        })
+    }
+
+    /// Position of the `pointer` master device, or the client one, with the
+    /// modifiers.
+    fn query_pointer(&self, pointer: Option<c_int>) -> Option<(f64, f64, Modifiers)> {
+        unsafe {
+            let mut deviceid = 0;
+            match pointer {
+                Some(pointer) => deviceid = pointer,
+                None => {
+                    if xinput2::XIGetClientPointer(self.display, 0, &mut deviceid) == FALSE {
+                        return None;
+                    }
+                }
+            }
+            let mut root = 0;
+            let mut child = 0;
+            let mut root_x = 0.0;
+            let mut root_y = 0.0;
+            let mut win_x = 0.0;
+            let mut win_y = 0.0;
+            let mut buttons = xinput2::XIButtonState::default();
+            let mut mods = xinput2::XIModifierState::default();
+            let mut group = xinput2::XIModifierState::default();
+            let same_screen = xinput2::XIQueryPointer(
+                self.display,
+                deviceid,
+                xlib::XDefaultRootWindow(self.display),
+                &mut root,
+                &mut child,
+                &mut root_x,
+                &mut root_y,
+                &mut win_x,
+                &mut win_y,
+                &mut buttons,
+                &mut mods,
+                &mut group,
+            );
+            if !buttons.mask.is_null() {
+                xlib::XFree(buttons.mask as *mut c_void);
+            }
+            if same_screen != TRUE {
+                return None;
+            }
+            Some((root_x, root_y, modifiers_from_state(mods.effective as _)))
+        }
+    }
+
+    fn device(&mut self, id: c_int) -> Option<DeviceInfo> {
+        if !self.devices.contains_key(&id) {
+            let info = self.query_device(id);
+            self.devices.insert(id, info);
+        }
+        self.devices[&id].clone()
+    }
+
+    /// Only devices backed by an evdev node have an id worth reporting, the
+    /// XTest ones don't.
+    fn query_device(&self, id: c_int) -> Option<DeviceInfo> {
+        let path = PathBuf::from(String::from_utf8(self.property(id, b"Device Node\0", 8)?).ok()?);
+        let event_id = path
+            .file_name()
+            .and_then(|name| name.to_str()?.strip_prefix("event")?.parse().ok())?;
+        let (name, use_) = unsafe {
+            let mut count = 0;
+            let info = xinput2::XIQueryDevice(self.display, id, &mut count);
+            if info.is_null() {
+                return None;
+            }
+            let device = (count > 0).then(|| {
+                let name = CStr::from_ptr((*info).name).to_string_lossy().into_owned();
+                (name, (*info)._use)
+            });
+            xinput2::XIFreeDeviceInfo(info);
+            device?
+        };
+        // CARD32 vendor and product.
+        let ids = self.property(id, b"Device Product ID\0", 32);
+        let id_at = |index: usize| -> Option<u16> {
+            let bytes = ids.as_ref()?.get(index * 4..index * 4 + 4)?;
+            Some(u32::from_ne_bytes(bytes.try_into().ok()?) as u16)
+        };
+        Some(DeviceInfo {
+            id: event_id,
+            path: Some(path),
+            name,
+            phys: None,
+            vendor: id_at(0).unwrap_or(0),
+            product: id_at(1).unwrap_or(0),
+            keyboard: use_ == xinput2::XISlaveKeyboard,
+            pointer: use_ == xinput2::XISlavePointer,
+        })
+    }
+
+    /// Raw bytes of a device property of the given format, without trailing
+    /// NULs.
+    fn property(&self, id: c_int, name: &[u8], format: c_int) -> Option<Vec<u8>> {
+        let name = CStr::from_bytes_with_nul(name).ok()?;
+        unsafe {
+            let atom = xlib::XInternAtom(self.display, name.as_ptr(), TRUE);
+            if atom == 0 {
+                return None;
+            }
+            let mut type_ = 0;
+            let mut actual_format = 0;
+            let mut items: c_ulong = 0;
+            let mut bytes_after = 0;
+            let mut data = null_mut();
+            if xinput2::XIGetProperty(
+                self.display,
+                id,
+                atom,
+                0,
+                1024,
+                FALSE,
+                xlib::AnyPropertyType as c_ulong,
+                &mut type_,
+                &mut actual_format,
+                &mut items,
+                &mut bytes_after,
+                &mut data,
+            ) != xlib::Success as c_int
+            {
+                return None;
+            }
+            if data.is_null() {
+                return None;
+            }
+            // XI2 hands the items over packed, unlike core properties.
+            let bytes = (actual_format == format).then(|| {
+                let len = items as usize * format as usize / 8;
+                let mut bytes = slice::from_raw_parts(data, len).to_vec();
+                while bytes.last() == Some(&0) && format == 8 {
+                    bytes.pop();
+                }
+                bytes
+            });
+            xlib::XFree(data as *mut c_void);
+            bytes
+        }
+    }
+}
+
+impl Drop for RawListener {
+    fn drop(&mut self) {
+        unsafe {
+            xlib::XCloseDisplay(self.display);
+        }
+    }
+}
+
+fn event_mask(events: &[c_int]) -> Vec<c_uchar> {
+    let mut mask = vec![0; (xinput2::XI_LASTEVENT as usize >> 3) + 1];
+    for event in events {
+        mask[*event as usize >> 3] |= 1 << (*event & 7);
+    }
+    mask
+}
+
+fn mask_is_set(mask: &[c_uchar], bit: usize) -> bool {
+    mask.get(bit >> 3)
+        .is_some_and(|byte| byte & (1 << (bit & 7)) != 0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_event_mask() {
+        let mask = event_mask(&[xinput2::XI_RawKeyPress, xinput2::XI_RawMotion]);
+        assert!(mask_is_set(&mask, xinput2::XI_RawKeyPress as usize));
+        assert!(mask_is_set(&mask, xinput2::XI_RawMotion as usize));
+        assert!(!mask_is_set(&mask, xinput2::XI_RawKeyRelease as usize));
+        assert!(!mask_is_set(&mask, 1000));
+    }
+}
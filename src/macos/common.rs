@@ -1,6 +1,6 @@
 #![allow(clippy::upper_case_acronyms)]
 use crate::macos::keyboard::Keyboard;
-use crate::rdev::{Button, Event, EventType};
+use crate::rdev::{Button, Event, EventType, Modifiers};
 use cocoa::base::id;
 use core_graphics::event::{CGEvent, CGEventFlags, CGEventTapLocation, CGEventType, EventField};
 use lazy_static::lazy_static;
@@ -75,8 +75,15 @@ extern "C" {
     pub fn CFRunLoopGetCurrent() -> CFRunLoopRef;
     pub fn CGEventTapEnable(tap: CFMachPortRef, enable: bool);
     pub fn CFRunLoopRun();
+    pub fn CFRunLoopRunInMode(
+        mode: CFRunLoopMode,
+        seconds: f64,
+        return_after_source_handled: bool,
+    ) -> i32;
+    pub fn CFRunLoopStop(rl: CFRunLoopRef);
 
     pub static kCFRunLoopCommonModes: CFRunLoopMode;
+    pub static kCFRunLoopDefaultMode: CFRunLoopMode;
 
 }
 pub type QCallback = unsafe extern "C" fn(
@@ -86,6 +93,23 @@ pub type QCallback = unsafe extern "C" fn(
     user_info: *mut c_void,
 ) -> CGEventRef;
 
+/// Flags are reported once the event is applied, including for FlagsChanged.
+fn modifiers_from_flags(flags: CGEventFlags) -> Modifiers {
+    let masks = [
+        (CGEventFlags::CGEventFlagShift, Modifiers::SHIFT),
+        (CGEventFlags::CGEventFlagControl, Modifiers::CTRL),
+        (CGEventFlags::CGEventFlagAlternate, Modifiers::ALT),
+        (CGEventFlags::CGEventFlagCommand, Modifiers::META),
+        (CGEventFlags::CGEventFlagAlphaShift, Modifiers::CAPS_LOCK),
+    ];
+    masks
+        .iter()
+        .filter(|(flag, _)| flags.contains(*flag))
+        .fold(Modifiers::empty(), |modifiers, (_, modifier)| {
+            modifiers | *modifier
+        })
+}
+
 pub unsafe fn convert(
     _type: CGEventType,
     cg_event: &CGEvent,
@@ -146,6 +170,8 @@ pub unsafe fn convert(
             event_type,
             time: SystemTime::now(),
             name,
+            modifiers: modifiers_from_flags(cg_event.get_flags()),
+            device: None,
         });
     }
     None
@@ -5,6 +5,11 @@ use cocoa::base::nil;
 use cocoa::foundation::NSAutoreleasePool;
 use core_graphics::event::{CGEventTapLocation, CGEventType};
 use std::os::raw::c_void;
+use std::panic;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc::sync_channel;
+use std::sync::Arc;
+use std::thread::{self, JoinHandle};
 
 static mut GLOBAL_CALLBACK: Option<Box<dyn FnMut(Event)>> = None;
 
@@ -32,6 +37,31 @@ unsafe extern "C" fn raw_callback(
     cg_event
 }
 
+/// Creates the event tap and adds it to the current run loop.
+unsafe fn add_tap() -> Result<CFMachPortRef, ListenError> {
+    let tap = CGEventTapCreate(
+        CGEventTapLocation::HID, // HID, Session, AnnotatedSession,
+        kCGHeadInsertEventTap,
+        CGEventTapOption::ListenOnly,
+        kCGEventMaskForAllEvents,
+        raw_callback,
+        nil,
+    );
+    if tap.is_null() {
+        return Err(ListenError::EventTapError);
+    }
+    let _loop = CFMachPortCreateRunLoopSource(nil, tap, 0);
+    if _loop.is_null() {
+        return Err(ListenError::LoopSourceError);
+    }
+
+    let current_loop = CFRunLoopGetCurrent();
+    CFRunLoopAddSource(current_loop, _loop, kCFRunLoopCommonModes);
+
+    CGEventTapEnable(tap, true);
+    Ok(tap)
+}
+
 pub fn listen<T>(callback: T) -> Result<(), ListenError>
 where
     T: FnMut(Event) + 'static,
@@ -39,27 +69,75 @@ where
     unsafe {
         GLOBAL_CALLBACK = Some(Box::new(callback));
         let _pool = NSAutoreleasePool::new(nil);
-        let tap = CGEventTapCreate(
-            CGEventTapLocation::HID, // HID, Session, AnnotatedSession,
-            kCGHeadInsertEventTap,
-            CGEventTapOption::ListenOnly,
-            kCGEventMaskForAllEvents,
-            raw_callback,
-            nil,
-        );
-        if tap.is_null() {
-            return Err(ListenError::EventTapError);
-        }
-        let _loop = CFMachPortCreateRunLoopSource(nil, tap, 0);
-        if _loop.is_null() {
-            return Err(ListenError::LoopSourceError);
+        add_tap()?;
+        CFRunLoopRun();
+    }
+    Ok(())
+}
+
+/// The run loop of the listening thread, `CFRunLoopStop` can be called on it
+/// from any thread.
+struct RunLoop(CFRunLoopRef);
+
+unsafe impl Send for RunLoop {}
+
+pub fn listen_with_handle<T>(callback: T) -> Result<ListenHandle, ListenError>
+where
+    T: FnMut(Event) + Send + 'static,
+{
+    let stopped = Arc::new(AtomicBool::new(false));
+    let thread_stopped = stopped.clone();
+    let (ready_send, ready_recv) = sync_channel(1);
+    let thread = thread::spawn(move || unsafe {
+        GLOBAL_CALLBACK = Some(Box::new(callback));
+        let _pool = NSAutoreleasePool::new(nil);
+        let tap = match add_tap() {
+            Ok(tap) => tap,
+            Err(error) => {
+                GLOBAL_CALLBACK = None;
+                ready_send.send(Err(error)).ok();
+                return Ok(());
+            }
+        };
+        ready_send.send(Ok(RunLoop(CFRunLoopGetCurrent()))).ok();
+        // Stopping the run loop only works once it runs, so we also wake up
+        // regularly to check whether we got stopped in between.
+        while !thread_stopped.load(Ordering::SeqCst) {
+            CFRunLoopRunInMode(kCFRunLoopDefaultMode, 1.0, false);
         }
+        CGEventTapEnable(tap, false);
+        GLOBAL_CALLBACK = None;
+        Ok(())
+    });
+    match ready_recv.recv() {
+        Ok(Ok(run_loop)) => Ok(ListenHandle {
+            stopped,
+            run_loop,
+            thread,
+        }),
+        Ok(Err(error)) => Err(error),
+        Err(_) => match thread.join() {
+            Ok(result) => result.and(Err(ListenError::EventTapError)),
+            Err(error) => panic::resume_unwind(error),
+        },
+    }
+}
 
-        let current_loop = CFRunLoopGetCurrent();
-        CFRunLoopAddSource(current_loop, _loop, kCFRunLoopCommonModes);
+/// Handle on a listener started with `listen_with_handle`.
+pub struct ListenHandle {
+    stopped: Arc<AtomicBool>,
+    run_loop: RunLoop,
+    thread: JoinHandle<Result<(), ListenError>>,
+}
 
-        CGEventTapEnable(tap, true);
-        CFRunLoopRun();
+impl ListenHandle {
+    /// Stops the listener and waits for the event tap to be disabled.
+    pub fn stop(self) -> Result<(), ListenError> {
+        self.stopped.store(true, Ordering::SeqCst);
+        unsafe { CFRunLoopStop(self.run_loop.0) };
+        match self.thread.join() {
+            Ok(result) => result,
+            Err(error) => panic::resume_unwind(error),
+        }
     }
-    Ok(())
 }
@@ -95,3 +95,28 @@ pub fn simulate(event_type: &EventType) -> Result<(), SimulateError> {
         }
     }
 }
+
+/// Sends events one after the other, there is no connection to keep open
+/// here, so this is the same as calling `simulate` for each event.
+pub struct Simulator {
+    _private: (),
+}
+
+impl Simulator {
+    pub fn new() -> Result<Simulator, SimulateError> {
+        Ok(Simulator { _private: () })
+    }
+
+    pub fn send(&mut self, event_type: &EventType) -> Result<(), SimulateError> {
+        simulate(event_type)
+    }
+
+    /// Sends all events in order, stops at the first event that can't be sent.
+    pub fn send_batch(&mut self, event_types: &[EventType]) -> Result<(), SimulateError> {
+        event_types.iter().try_for_each(simulate)
+    }
+
+    pub fn flush(&mut self) -> Result<(), SimulateError> {
+        Ok(())
+    }
+}
@@ -0,0 +1,76 @@
+#[cfg(feature = "unstable_grab")]
+use crate::rdev::GrabError;
+use crate::rdev::{
+    Backend, BackendStatus, Capabilities, Event, EventType, ListenError, SimulateError, Unavailable,
+};
+
+/// The one backend MacOS and Windows have.
+#[cfg(target_os = "macos")]
+const NATIVE: Backend = Backend::CoreGraphics;
+#[cfg(target_os = "windows")]
+const NATIVE: Backend = Backend::Win32;
+
+/// Tells which backends `listen`, `grab` and `simulate` can use, which is
+/// only the platform's own. Grabbing needs the `unstable_grab` feature, and
+/// MacOS doesn't tell whether accessibility is enabled.
+///
+/// ```no_run
+/// use rdev::capabilities;
+///
+/// for status in capabilities().listen {
+///     match status.status {
+///         Ok(()) => println!("{:?} can listen", status.backend),
+///         Err(why) => println!("{:?} can't listen: {}", status.backend, why),
+///     }
+/// }
+/// ```
+pub fn capabilities() -> Capabilities {
+    let grab = if cfg!(feature = "unstable_grab") {
+        Ok(())
+    } else {
+        Err(Unavailable::MissingFeature("unstable_grab"))
+    };
+    Capabilities {
+        listen: vec![status(Ok(()))],
+        grab: vec![status(grab)],
+        simulate: vec![status(Ok(()))],
+    }
+}
+
+fn status(status: Result<(), Unavailable>) -> BackendStatus {
+    BackendStatus {
+        backend: NATIVE,
+        status,
+    }
+}
+
+/// Same as `listen`, as long as `backend` is the platform's own.
+pub fn listen_with<T>(backend: Backend, callback: T) -> Result<(), ListenError>
+where
+    T: FnMut(Event) + 'static,
+{
+    if backend != NATIVE {
+        return Err(ListenError::UnsupportedBackend);
+    }
+    crate::listen(callback)
+}
+
+/// Same as `simulate`, as long as `backend` is the platform's own.
+pub fn simulate_with(backend: Backend, event_type: &EventType) -> Result<(), SimulateError> {
+    if backend != NATIVE {
+        return Err(SimulateError);
+    }
+    crate::simulate(event_type)
+}
+
+/// Same as `grab`, as long as `backend` is the platform's own.
+#[cfg(feature = "unstable_grab")]
+pub fn grab_with<T>(backend: Backend, callback: T) -> Result<(), GrabError>
+where
+    T: FnMut(Event) -> Option<Event> + 'static,
+{
+    if backend != NATIVE {
+        return Err(GrabError::UnsupportedBackend);
+    }
+    crate::grab(callback)
+}
@@ -1,5 +1,7 @@
+use bitflags::bitflags;
 #[cfg(feature = "serialize")]
 use serde::{Deserialize, Serialize};
+use std::path::PathBuf;
 use std::time::SystemTime;
 use std::{fmt, fmt::Display};
 
@@ -29,6 +31,12 @@ pub enum ListenError {
     RecordContextError,
     /// Linux
     XRecordExtensionError,
+    /// Linux
+    XInputExtensionError,
+    /// All, `listen_with` or `set_listen_backend` a backend that can't listen.
+    UnsupportedBackend,
+    /// Linux, Windows
+    IoError(std::io::Error),
     /// Windows
     KeyHookError(u32),
     /// Windows
@@ -49,6 +57,10 @@ pub enum GrabError {
     MissingDisplayError,
     /// Linux
     KeyboardError,
+    /// Linux, the `GrabConfig` matches none of the devices plugged in.
+    NoDeviceError,
+    /// All, `grab_with` a backend that can't grab.
+    UnsupportedBackend,
     /// Windows
     KeyHookError(u32),
     /// Windows
@@ -57,6 +69,102 @@ pub enum GrabError {
     SimulateError,
     IoError(std::io::Error),
 }
+
+/// The ways to listen, grab or simulate events, see `capabilities`. MacOS
+/// and Windows have a single one.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+#[non_exhaustive]
+pub enum Backend {
+    /// Linux, X11 RECORD extension, listens. The events are seen the way
+    /// clients do.
+    Record,
+    /// Linux, X11 XInput2 raw events, listens. The events tell the device
+    /// they come from and keep coming while another client grabs the keyboard,
+    /// but held keys don't repeat, and the mouse position is asked to the X
+    /// server after each motion, so it lags a little behind.
+    XInput2,
+    /// Linux, X11 XTest extension, simulates.
+    XTest,
+    /// Linux, `/dev/input/event*` devices, listens and grabs. Works without
+    /// X11, but needs read access to the devices, usually through the `input`
+    /// group.
+    Evdev,
+    /// Linux, virtual devices from `/dev/uinput`, simulates. Seen by X11,
+    /// Wayland and the virtual console alike, but needs write access to
+    /// `/dev/uinput`.
+    Uinput,
+    /// MacOS, Quartz event taps and events, listens, grabs and simulates.
+    CoreGraphics,
+    /// Windows, hooks and `SendInput`, listens, grabs and simulates.
+    Win32,
+}
+
+/// Why a backend can't be used.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum Unavailable {
+    /// rdev was built without the feature the backend needs.
+    MissingFeature(&'static str),
+    /// `DISPLAY` is unset.
+    NoDisplay,
+    /// The X server `DISPLAY` points to can't be reached.
+    DisplayUnreachable,
+    /// The X server lacks the extension.
+    MissingExtension(&'static str),
+    /// There's no such device file.
+    MissingDevice(PathBuf),
+    /// The device file can't be opened. `group` owns it when the user isn't
+    /// part of it.
+    PermissionDenied {
+        path: PathBuf,
+        group: Option<String>,
+    },
+    /// Anything else that went wrong.
+    Error(String),
+}
+
+impl Display for Unavailable {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Unavailable::MissingFeature(feature) => write!(f, "{} feature disabled", feature),
+            Unavailable::NoDisplay => write!(f, "no DISPLAY"),
+            Unavailable::DisplayUnreachable => write!(f, "cannot connect to DISPLAY"),
+            Unavailable::MissingExtension(name) => write!(f, "{} extension missing", name),
+            Unavailable::MissingDevice(path) => write!(f, "no {}", path.display()),
+            Unavailable::PermissionDenied { path, group } => {
+                write!(f, "permission denied on {}", path.display())?;
+                match group {
+                    Some(group) => write!(f, " (user not in {} group)", group),
+                    None => Ok(()),
+                }
+            }
+            Unavailable::Error(error) => write!(f, "{}", error),
+        }
+    }
+}
+
+/// Whether `backend` can be used right now, see `capabilities`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BackendStatus {
+    pub backend: Backend,
+    pub status: Result<(), Unavailable>,
+}
+
+impl BackendStatus {
+    pub fn is_available(&self) -> bool {
+        self.status.is_ok()
+    }
+}
+
+/// The backends each function can use, in the order rdev tries them with
+/// `DISPLAY` set when none was picked.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Capabilities {
+    pub listen: Vec<BackendStatus>,
+    pub grab: Vec<BackendStatus>,
+    pub simulate: Vec<BackendStatus>,
+}
+
 /// Errors that occur when trying to get display size.
 #[non_exhaustive]
 #[derive(Debug)]
@@ -71,6 +179,34 @@ impl From<SimulateError> for GrabError {
     }
 }
 
+/// `io::Error` can't be cloned, the copy keeps its kind and message.
+impl Clone for ListenError {
+    fn clone(&self) -> ListenError {
+        match self {
+            ListenError::EventTapError => ListenError::EventTapError,
+            ListenError::LoopSourceError => ListenError::LoopSourceError,
+            ListenError::MissingDisplayError => ListenError::MissingDisplayError,
+            ListenError::KeyboardError => ListenError::KeyboardError,
+            ListenError::RecordContextEnablingError => ListenError::RecordContextEnablingError,
+            ListenError::RecordContextError => ListenError::RecordContextError,
+            ListenError::XRecordExtensionError => ListenError::XRecordExtensionError,
+            ListenError::XInputExtensionError => ListenError::XInputExtensionError,
+            ListenError::UnsupportedBackend => ListenError::UnsupportedBackend,
+            ListenError::IoError(error) => {
+                ListenError::IoError(std::io::Error::new(error.kind(), error.to_string()))
+            }
+            ListenError::KeyHookError(code) => ListenError::KeyHookError(*code),
+            ListenError::MouseHookError(code) => ListenError::MouseHookError(*code),
+        }
+    }
+}
+
+impl From<std::io::Error> for ListenError {
+    fn from(err: std::io::Error) -> ListenError {
+        ListenError::IoError(err)
+    }
+}
+
 impl From<std::io::Error> for GrabError {
     fn from(err: std::io::Error) -> GrabError {
         GrabError::IoError(err)
@@ -89,6 +225,28 @@ impl Display for SimulateError {
 
 impl std::error::Error for SimulateError {}
 
+/// Errors that occur when parsing a `Hotkey` like "ctrl+shift+k" or a `Sequence`
+/// like "ctrl+x ctrl+s".
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum ParseHotkeyError {
+    /// The string, or one of its `+` separated parts, is empty.
+    MissingKey,
+    /// No key has that name.
+    UnknownKey(String),
+}
+
+impl Display for ParseHotkeyError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ParseHotkeyError::MissingKey => write!(f, "Missing key in hotkey"),
+            ParseHotkeyError::UnknownKey(name) => write!(f, "Unknown key {:?} in hotkey", name),
+        }
+    }
+}
+
+impl std::error::Error for ParseHotkeyError {}
+
 /// Key names based on physical location on the device
 /// Merge Option(MacOS) and Alt(Windows, Linux) into Alt
 /// Merge Windows (Windows), Meta(Linux), Command(MacOS) into Meta
@@ -252,18 +410,89 @@ pub enum EventType {
     },
 }
 
+bitflags! {
+    /// Modifiers held down, and locks turned on, when an event happened. The
+    /// event's own key is already accounted for: pressing shift gives SHIFT.
+    #[derive(Default)]
+    #[cfg_attr(feature = "serialize", derive(Serialize, Deserialize))]
+    pub struct Modifiers: u16 {
+        const SHIFT = 1;
+        const CTRL = 1 << 1;
+        const ALT = 1 << 2;
+        /// Not on MacOS, where the option key is ALT.
+        const ALT_GR = 1 << 3;
+        const META = 1 << 4;
+        const CAPS_LOCK = 1 << 5;
+        /// Not on MacOS.
+        const NUM_LOCK = 1 << 6;
+    }
+}
+
+impl Modifiers {
+    /// Applies the key of `event_type`, for platforms reporting the modifiers
+    /// from before the event.
+    #[cfg(not(target_os = "macos"))]
+    pub(crate) fn update(&mut self, event_type: &EventType) {
+        let (key, pressed) = match event_type {
+            EventType::KeyPress(key) => (key, true),
+            EventType::KeyRelease(key) => (key, false),
+            _ => return,
+        };
+        let modifier = match key {
+            Key::ShiftLeft | Key::ShiftRight => Modifiers::SHIFT,
+            Key::ControlLeft | Key::ControlRight => Modifiers::CTRL,
+            Key::Alt => Modifiers::ALT,
+            Key::AltGr => Modifiers::ALT_GR,
+            Key::MetaLeft | Key::MetaRight => Modifiers::META,
+            Key::CapsLock if pressed => return self.toggle(Modifiers::CAPS_LOCK),
+            Key::NumLock if pressed => return self.toggle(Modifiers::NUM_LOCK),
+            _ => return,
+        };
+        self.set(modifier, pressed);
+    }
+}
+
 /// When events arrive from the OS they get some additional information added from
 /// EventType, which is the time when this event was received, and the name Option
 /// which contains what characters should be emmitted from that event. This relies
 /// on the OS layout and keyboard state machinery.
-/// Caveat: Dead keys don't function on Linux(X11) yet. You will receive None for
-/// a dead key, and the raw letter instead of accentuated letter.
+/// A dead key or a key in the middle of a Compose sequence gets None, the key
+/// completing it gets the composed string.
+/// Caveat: On Linux (Xlib), dead keys only function once the program set its
+/// locale, with `setlocale(LC_ALL, "")`.
 #[derive(Debug, Clone, PartialEq)]
 #[cfg_attr(feature = "serialize", derive(Serialize, Deserialize))]
 pub struct Event {
     pub time: SystemTime,
     pub name: Option<String>,
     pub event_type: EventType,
+    /// Tracked from the key events on Linux and Windows, read from the
+    /// event's flags on MacOS.
+    #[cfg_attr(feature = "serialize", serde(default))]
+    pub modifiers: Modifiers,
+    /// The device the event came from, when the backend can tell.
+    #[cfg_attr(feature = "serialize", serde(default))]
+    pub device: Option<DeviceInfo>,
+}
+
+/// An input device, as seen by the OS.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serialize", derive(Serialize, Deserialize))]
+pub struct DeviceInfo {
+    /// Identifies the device while it's plugged in: N in /dev/input/eventN
+    /// on Linux.
+    pub id: u32,
+    /// The device file, /dev/input/eventN on Linux.
+    pub path: Option<PathBuf>,
+    pub name: String,
+    /// Where the device is plugged in, like usb-0000:00:14.0-2/input0.
+    pub phys: Option<String>,
+    pub vendor: u16,
+    pub product: u16,
+    /// Has letter keys.
+    pub keyboard: bool,
+    /// Moves the pointer, mice as well as touchpads and tablets.
+    pub pointer: bool,
 }
 
 /// We can define a dummy Keyboard, that we will use to detect
@@ -271,9 +500,11 @@ pub struct Event {
 /// layout for now !
 /// Caveat : This is layout dependent. If your app needs to support
 /// layout switching don't use this !
-/// Caveat: On Linux, the dead keys mechanism is not implemented.
-/// Caveat: Only shift and dead keys are implemented, Alt+unicode code on windows
-/// won't work.
+/// Caveat: On Linux (Xlib), the dead keys mechanism needs the program to set its
+/// locale first, with `setlocale(LC_ALL, "")`.
+/// Caveat: On Linux, Shift, CapsLock, Alt, AltGr, Meta and Control are tracked, all
+/// but Control change the name. Elsewhere, only shift and dead keys are implemented,
+/// Alt+Unicode code on Windows won't work.
 ///
 /// ```no_run
 /// use rdev::{Keyboard, EventType, Key, KeyboardState};
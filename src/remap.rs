@@ -0,0 +1,393 @@
+#[cfg(all(feature = "unstable_grab", target_os = "linux"))]
+use crate::rdev::GrabError;
+use crate::rdev::{EventType, Key};
+use std::collections::HashMap;
+use std::time::{Duration, Instant};
+
+/// Identifies a layer added with `Remapper::add_layer`.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub struct Layer(usize);
+
+/// What a remapped key does.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Action {
+    /// Acts as another key, like CapsLock as Escape.
+    Key(Key),
+    /// Acts as `hold` when held down while pressing other keys or past the tap
+    /// timeout, as `tap` when pressed and released on its own within the tap
+    /// timeout. CapsLock as Escape when tapped and Control when held.
+    TapHold { tap: Key, hold: Key },
+    /// Turns the layer on, or back off.
+    ToggleLayer(Layer),
+    /// Sends these events on press, and nothing on release.
+    Macro(Vec<EventType>),
+}
+
+/// How a pressed key got resolved, so that its release matches even if the
+/// layers changed in between.
+#[derive(Debug)]
+enum Pressed {
+    Key(Key),
+    TapHold {
+        tap: Key,
+        hold: Key,
+        time: Instant,
+        /// Whether it acts as `hold`, decided once another key gets pressed
+        /// or once it repeats past the tap timeout.
+        holding: bool,
+    },
+    Nothing,
+}
+
+/// Remaps keys in a stream of events, keyd or kmonad style: key swaps,
+/// tap-hold keys, layers and macros. Feed it events with `process`, or let it
+/// drive `grab` on Linux.
+///
+/// There is no timer: a tap-hold key held on its own turns into `hold` with
+/// its first key repeat past the tap timeout, as sent by the keyboard. Without
+/// key repeats, it is only resolved by its release or by the next press.
+///
+/// ```
+/// use rdev::{Action, EventType, Key, Remapper};
+///
+/// let mut remapper = Remapper::new();
+/// remapper.map(
+///     Key::CapsLock,
+///     Action::TapHold {
+///         tap: Key::Escape,
+///         hold: Key::ControlLeft,
+///     },
+/// );
+/// let navigation = remapper.add_layer();
+/// remapper.map(Key::ScrollLock, Action::ToggleLayer(navigation));
+/// remapper.map_in(navigation, Key::KeyH, Action::Key(Key::LeftArrow));
+///
+/// remapper.process(&EventType::KeyPress(Key::ScrollLock));
+/// let events = remapper.process(&EventType::KeyPress(Key::KeyH));
+/// assert_eq!(events, vec![EventType::KeyPress(Key::LeftArrow)]);
+/// ```
+pub struct Remapper {
+    /// The base layer comes first.
+    layers: Vec<HashMap<Key, Action>>,
+    /// Active layers on top of the base one, most recently activated last.
+    active: Vec<Layer>,
+    pressed: HashMap<Key, Pressed>,
+    tap_timeout: Duration,
+}
+
+impl Default for Remapper {
+    fn default() -> Remapper {
+        Remapper {
+            layers: vec![HashMap::new()],
+            active: vec![],
+            pressed: HashMap::new(),
+            tap_timeout: Duration::from_millis(200),
+        }
+    }
+}
+
+impl Remapper {
+    pub fn new() -> Remapper {
+        Remapper::default()
+    }
+
+    /// Longest press of a tap-hold key still counting as a tap, 200ms by
+    /// default.
+    pub fn set_tap_timeout(&mut self, tap_timeout: Duration) {
+        self.tap_timeout = tap_timeout;
+    }
+
+    /// Adds an empty layer, inactive until toggled.
+    pub fn add_layer(&mut self) -> Layer {
+        self.layers.push(HashMap::new());
+        Layer(self.layers.len() - 1)
+    }
+
+    /// Remaps `key` in the base layer.
+    pub fn map(&mut self, key: Key, action: Action) {
+        self.layers[0].insert(key, action);
+    }
+
+    /// Remaps `key` while `layer` is active. Keys missing from the active
+    /// layers act as in the base layer.
+    pub fn map_in(&mut self, layer: Layer, key: Key, action: Action) {
+        self.layers[layer.0].insert(key, action);
+    }
+
+    pub fn process(&mut self, event_type: &EventType) -> Vec<EventType> {
+        self.process_at(event_type, Instant::now())
+    }
+
+    /// Same as `process`, for an event that happened at `time`. Returns the
+    /// events to send instead.
+    pub fn process_at(&mut self, event_type: &EventType, time: Instant) -> Vec<EventType> {
+        match event_type {
+            EventType::KeyPress(key) => self.press(*key, time),
+            EventType::KeyRelease(key) => self.release(*key, time),
+            EventType::ButtonPress(_) => {
+                // Ctrl + click with a tap-hold control.
+                let mut events = self.hold_pending();
+                events.push(*event_type);
+                events
+            }
+            _ => vec![*event_type],
+        }
+    }
+
+    fn action(&self, key: Key) -> Option<&Action> {
+        self.active
+            .iter()
+            .rev()
+            .map(|layer| &self.layers[layer.0])
+            .chain(self.layers.first())
+            .find_map(|layer| layer.get(&key))
+    }
+
+    /// Tap-hold keys still down act as `hold` once something else happens.
+    fn hold_pending(&mut self) -> Vec<EventType> {
+        let mut events = vec![];
+        for pressed in self.pressed.values_mut() {
+            if let Pressed::TapHold { hold, holding, .. } = pressed {
+                if !*holding {
+                    *holding = true;
+                    events.push(EventType::KeyPress(*hold));
+                }
+            }
+        }
+        events
+    }
+
+    fn press(&mut self, key: Key, time: Instant) -> Vec<EventType> {
+        let tap_timeout = self.tap_timeout;
+        if let Some(pressed) = self.pressed.get_mut(&key) {
+            // Key repeats
+            return match pressed {
+                Pressed::Key(key) => vec![EventType::KeyPress(*key)],
+                Pressed::TapHold {
+                    hold,
+                    time: press,
+                    holding,
+                    ..
+                } => {
+                    // Held on its own for too long to be a tap.
+                    if time.saturating_duration_since(*press) > tap_timeout {
+                        *holding = true;
+                    }
+                    if *holding {
+                        vec![EventType::KeyPress(*hold)]
+                    } else {
+                        vec![]
+                    }
+                }
+                Pressed::Nothing => vec![],
+            };
+        }
+        let mut events = self.hold_pending();
+        let pressed = match self.action(key).cloned() {
+            None => {
+                events.push(EventType::KeyPress(key));
+                Pressed::Key(key)
+            }
+            Some(Action::Key(key)) => {
+                events.push(EventType::KeyPress(key));
+                Pressed::Key(key)
+            }
+            Some(Action::TapHold { tap, hold }) => Pressed::TapHold {
+                tap,
+                hold,
+                time,
+                holding: false,
+            },
+            Some(Action::ToggleLayer(layer)) => {
+                match self.active.iter().position(|active| *active == layer) {
+                    Some(index) => {
+                        self.active.remove(index);
+                    }
+                    None => self.active.push(layer),
+                }
+                Pressed::Nothing
+            }
+            Some(Action::Macro(macro_events)) => {
+                events.extend(macro_events);
+                Pressed::Nothing
+            }
+        };
+        self.pressed.insert(key, pressed);
+        events
+    }
+
+    fn release(&mut self, key: Key, time: Instant) -> Vec<EventType> {
+        match self.pressed.remove(&key) {
+            // Pressed before we started.
+            None => vec![EventType::KeyRelease(key)],
+            Some(Pressed::Key(key)) => vec![EventType::KeyRelease(key)],
+            Some(Pressed::TapHold {
+                hold,
+                holding: true,
+                ..
+            }) => vec![EventType::KeyRelease(hold)],
+            Some(Pressed::TapHold {
+                tap, time: press, ..
+            }) => {
+                if time.saturating_duration_since(press) <= self.tap_timeout {
+                    vec![EventType::KeyPress(tap), EventType::KeyRelease(tap)]
+                } else {
+                    vec![]
+                }
+            }
+            Some(Pressed::Nothing) => vec![],
+        }
+    }
+
+    /// Grabs global events and sends them remapped, see `grab`.
+    #[cfg(all(feature = "unstable_grab", target_os = "linux"))]
+    pub fn grab(self) -> Result<(), GrabError> {
+        crate::linux::remap(self)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn caps_esc_ctrl() -> Remapper {
+        let mut remapper = Remapper::new();
+        remapper.map(
+            Key::CapsLock,
+            Action::TapHold {
+                tap: Key::Escape,
+                hold: Key::ControlLeft,
+            },
+        );
+        remapper
+    }
+
+    #[test]
+    fn test_swap() {
+        let mut remapper = Remapper::new();
+        remapper.map(Key::CapsLock, Action::Key(Key::Escape));
+        assert_eq!(
+            remapper.process(&EventType::KeyPress(Key::CapsLock)),
+            vec![EventType::KeyPress(Key::Escape)]
+        );
+        assert_eq!(
+            remapper.process(&EventType::KeyRelease(Key::CapsLock)),
+            vec![EventType::KeyRelease(Key::Escape)]
+        );
+        assert_eq!(
+            remapper.process(&EventType::KeyPress(Key::KeyA)),
+            vec![EventType::KeyPress(Key::KeyA)]
+        );
+    }
+
+    #[test]
+    fn test_tap_hold() {
+        let mut remapper = caps_esc_ctrl();
+        let start = Instant::now();
+        let later = start + Duration::from_millis(100);
+        assert!(remapper
+            .process_at(&EventType::KeyPress(Key::CapsLock), start)
+            .is_empty());
+        assert_eq!(
+            remapper.process_at(&EventType::KeyRelease(Key::CapsLock), later),
+            vec![
+                EventType::KeyPress(Key::Escape),
+                EventType::KeyRelease(Key::Escape)
+            ]
+        );
+
+        remapper.process_at(&EventType::KeyPress(Key::CapsLock), start);
+        assert_eq!(
+            remapper.process_at(&EventType::KeyPress(Key::KeyC), start),
+            vec![
+                EventType::KeyPress(Key::ControlLeft),
+                EventType::KeyPress(Key::KeyC)
+            ]
+        );
+        remapper.process_at(&EventType::KeyRelease(Key::KeyC), later);
+        assert_eq!(
+            remapper.process_at(&EventType::KeyRelease(Key::CapsLock), later),
+            vec![EventType::KeyRelease(Key::ControlLeft)]
+        );
+
+        // Held too long to be a tap.
+        remapper.process_at(&EventType::KeyPress(Key::CapsLock), start);
+        let too_late = start + Duration::from_secs(1);
+        assert!(remapper
+            .process_at(&EventType::KeyRelease(Key::CapsLock), too_late)
+            .is_empty());
+    }
+
+    #[test]
+    fn test_tap_hold_repeat() {
+        let mut remapper = caps_esc_ctrl();
+        let start = Instant::now();
+        remapper.process_at(&EventType::KeyPress(Key::CapsLock), start);
+        // Repeats within the tap timeout don't decide anything yet.
+        let soon = start + Duration::from_millis(100);
+        assert!(remapper
+            .process_at(&EventType::KeyPress(Key::CapsLock), soon)
+            .is_empty());
+        let later = start + Duration::from_millis(300);
+        assert_eq!(
+            remapper.process_at(&EventType::KeyPress(Key::CapsLock), later),
+            vec![EventType::KeyPress(Key::ControlLeft)]
+        );
+        assert_eq!(
+            remapper.process_at(&EventType::KeyRelease(Key::CapsLock), later),
+            vec![EventType::KeyRelease(Key::ControlLeft)]
+        );
+    }
+
+    #[test]
+    fn test_layers() {
+        let mut remapper = Remapper::new();
+        let navigation = remapper.add_layer();
+        remapper.map(Key::ScrollLock, Action::ToggleLayer(navigation));
+        remapper.map_in(navigation, Key::KeyH, Action::Key(Key::LeftArrow));
+
+        assert!(remapper
+            .process(&EventType::KeyPress(Key::ScrollLock))
+            .is_empty());
+        assert!(remapper
+            .process(&EventType::KeyRelease(Key::ScrollLock))
+            .is_empty());
+        assert_eq!(
+            remapper.process(&EventType::KeyPress(Key::KeyH)),
+            vec![EventType::KeyPress(Key::LeftArrow)]
+        );
+        // Keys absent from the layer fall back to the base one.
+        assert_eq!(
+            remapper.process(&EventType::KeyPress(Key::KeyJ)),
+            vec![EventType::KeyPress(Key::KeyJ)]
+        );
+
+        remapper.process(&EventType::KeyPress(Key::ScrollLock));
+        // Released as it was pressed, even with the layer off.
+        assert_eq!(
+            remapper.process(&EventType::KeyRelease(Key::KeyH)),
+            vec![EventType::KeyRelease(Key::LeftArrow)]
+        );
+        assert_eq!(
+            remapper.process(&EventType::KeyPress(Key::KeyH)),
+            vec![EventType::KeyPress(Key::KeyH)]
+        );
+    }
+
+    #[test]
+    fn test_macro() {
+        let mut remapper = Remapper::new();
+        let hi = vec![
+            EventType::KeyPress(Key::ShiftLeft),
+            EventType::KeyPress(Key::KeyH),
+            EventType::KeyRelease(Key::KeyH),
+            EventType::KeyRelease(Key::ShiftLeft),
+            EventType::KeyPress(Key::KeyI),
+            EventType::KeyRelease(Key::KeyI),
+        ];
+        remapper.map(Key::F5, Action::Macro(hi.clone()));
+        assert_eq!(remapper.process(&EventType::KeyPress(Key::F5)), hi);
+        assert!(remapper.process(&EventType::KeyPress(Key::F5)).is_empty());
+        assert!(remapper.process(&EventType::KeyRelease(Key::F5)).is_empty());
+    }
+}
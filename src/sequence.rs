@@ -0,0 +1,476 @@
+use crate::hotkey::{key_from_name, normalize};
+use crate::rdev::{EventType, Key, ParseHotkeyError};
+use std::collections::HashSet;
+use std::str::FromStr;
+use std::time::{Duration, Instant};
+
+/// Keys pressed simultaneously, in any order. Left and right modifiers are
+/// equivalent, except for Alt and AltGr.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Chord {
+    keys: HashSet<Key>,
+}
+
+impl Chord {
+    pub fn new<I>(keys: I) -> Chord
+    where
+        I: IntoIterator<Item = Key>,
+    {
+        Chord {
+            keys: keys.into_iter().map(normalize).collect(),
+        }
+    }
+
+    pub fn keys(&self) -> impl Iterator<Item = &Key> {
+        self.keys.iter()
+    }
+}
+
+/// Parses keys separated by `+`, like "ctrl+x" or "j+k".
+impl FromStr for Chord {
+    type Err = ParseHotkeyError;
+
+    fn from_str(s: &str) -> Result<Chord, ParseHotkeyError> {
+        let keys = s
+            .split('+')
+            .map(key_from_name)
+            .collect::<Result<Vec<Key>, ParseHotkeyError>>()?;
+        Ok(Chord::new(keys))
+    }
+}
+
+/// Chords typed one after the other.
+///
+/// ```
+/// use rdev::{Chord, Key, Sequence};
+///
+/// let save = Sequence::new(vec![
+///     Chord::new([Key::ControlLeft, Key::KeyX]),
+///     Chord::new([Key::ControlLeft, Key::KeyS]),
+/// ]);
+/// assert_eq!("ctrl+x ctrl+s".parse(), Ok(save));
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Sequence {
+    chords: Vec<Chord>,
+}
+
+impl Sequence {
+    pub fn new(chords: Vec<Chord>) -> Sequence {
+        Sequence { chords }
+    }
+
+    pub fn chords(&self) -> &[Chord] {
+        &self.chords
+    }
+}
+
+/// Parses chords separated by whitespace, like "ctrl+x ctrl+s" or "space f f".
+impl FromStr for Sequence {
+    type Err = ParseHotkeyError;
+
+    fn from_str(s: &str) -> Result<Sequence, ParseHotkeyError> {
+        let chords = s
+            .split_whitespace()
+            .map(Chord::from_str)
+            .collect::<Result<Vec<Chord>, ParseHotkeyError>>()?;
+        if chords.is_empty() {
+            return Err(ParseHotkeyError::MissingKey);
+        }
+        Ok(Sequence::new(chords))
+    }
+}
+
+/// Identifies a sequence registered on a `SequenceMatcher`.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub struct SequenceId(u64);
+
+/// Outcome of an event fed to a `SequenceMatcher`.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub enum SequenceMatch {
+    /// No sequence is in progress.
+    NoMatch,
+    /// Part of a sequence got typed, see `SequenceMatcher::pending`.
+    Partial,
+    /// The whole sequence got typed.
+    Complete(SequenceId),
+}
+
+/// Trie of the registered sequences, one chord per level.
+#[derive(Default)]
+struct Node {
+    children: Vec<(Chord, Node)>,
+    id: Option<SequenceId>,
+}
+
+impl Node {
+    /// Returns whether `id` was found.
+    fn remove(&mut self, id: SequenceId) -> bool {
+        let mut found = self.id == Some(id);
+        if found {
+            self.id = None;
+        }
+        for (_, child) in &mut self.children {
+            found |= child.remove(id);
+        }
+        self.children
+            .retain(|(_, child)| child.id.is_some() || !child.children.is_empty());
+        found
+    }
+}
+
+/// Detects multi-stroke bindings like "ctrl+x ctrl+s" or "space f f" from key
+/// events. Each chord must follow the previous one within the timeout.
+///
+/// Keys still held from the previous chord don't get in the way of the next
+/// one, so "ctrl+x ctrl+s" can be typed without releasing ctrl. The first
+/// chord must be matched exactly though: "x" does not match while ctrl is held.
+///
+/// ```
+/// use rdev::{EventType, Key, SequenceMatch, SequenceMatcher};
+/// use std::time::Duration;
+///
+/// let mut matcher = SequenceMatcher::new(Duration::from_secs(1));
+/// let save = matcher.register("ctrl+x ctrl+s".parse().unwrap()).unwrap();
+///
+/// matcher.handle(&EventType::KeyPress(Key::ControlLeft));
+/// matcher.handle(&EventType::KeyPress(Key::KeyX));
+/// assert_eq!(matcher.pending().len(), 1);
+/// assert_eq!(
+///     matcher.handle(&EventType::KeyPress(Key::KeyS)),
+///     SequenceMatch::Complete(save)
+/// );
+/// ```
+pub struct SequenceMatcher {
+    timeout: Duration,
+    next_id: u64,
+    root: Node,
+    /// Indices of the chords typed so far, from the root.
+    path: Vec<usize>,
+    pressed: HashSet<Key>,
+    /// Keys held when the last chord got typed.
+    carryover: HashSet<Key>,
+    last: Option<Instant>,
+}
+
+impl SequenceMatcher {
+    pub fn new(timeout: Duration) -> SequenceMatcher {
+        SequenceMatcher {
+            timeout,
+            next_id: 0,
+            root: Node::default(),
+            path: vec![],
+            pressed: HashSet::new(),
+            carryover: HashSet::new(),
+            last: None,
+        }
+    }
+
+    /// Returns None if `sequence` is empty, already registered, or starts
+    /// with another registered sequence (or is the start of one), as it could
+    /// never be told apart.
+    pub fn register(&mut self, sequence: Sequence) -> Option<SequenceId> {
+        if sequence.chords.is_empty() {
+            return None;
+        }
+        let mut node = &self.root;
+        let mut known = true;
+        for chord in &sequence.chords {
+            match node.children.iter().find(|(child, _)| child == chord) {
+                Some((_, child)) if child.id.is_some() => return None,
+                Some((_, child)) => node = child,
+                None => {
+                    known = false;
+                    break;
+                }
+            }
+        }
+        if known {
+            return None;
+        }
+
+        let id = SequenceId(self.next_id);
+        self.next_id += 1;
+        let mut node = &mut self.root;
+        for chord in sequence.chords {
+            let index = match node.children.iter().position(|(child, _)| *child == chord) {
+                Some(index) => index,
+                None => {
+                    node.children.push((chord, Node::default()));
+                    node.children.len() - 1
+                }
+            };
+            node = &mut node.children[index].1;
+        }
+        node.id = Some(id);
+        self.reset();
+        Some(id)
+    }
+
+    /// Returns false if `id` was not registered.
+    pub fn unregister(&mut self, id: SequenceId) -> bool {
+        let found = self.root.remove(id);
+        self.reset();
+        found
+    }
+
+    /// Chords of the sequences in progress typed so far, for instance to show
+    /// them in a status bar.
+    pub fn pending(&self) -> Vec<&Chord> {
+        let mut node = &self.root;
+        self.path
+            .iter()
+            .map(|index| {
+                let (chord, child) = &node.children[*index];
+                node = child;
+                chord
+            })
+            .collect()
+    }
+
+    /// Drops the sequence in progress.
+    pub fn reset(&mut self) {
+        self.path.clear();
+        self.carryover.clear();
+        self.last = None;
+    }
+
+    /// Drops the sequence in progress if its timeout expired at `now`, returns
+    /// whether it did.
+    pub fn expire(&mut self, now: Instant) -> bool {
+        match self.last {
+            Some(last) if now.saturating_duration_since(last) > self.timeout => {
+                self.reset();
+                true
+            }
+            _ => false,
+        }
+    }
+
+    pub fn handle(&mut self, event_type: &EventType) -> SequenceMatch {
+        self.handle_at(event_type, Instant::now())
+    }
+
+    /// Same as `handle`, for an event that happened at `time`.
+    pub fn handle_at(&mut self, event_type: &EventType, time: Instant) -> SequenceMatch {
+        self.expire(time);
+        match event_type {
+            EventType::KeyPress(key) => {
+                let key = normalize(*key);
+                // Key repeats
+                if !self.pressed.insert(key) {
+                    return self.state();
+                }
+                self.last = Some(time);
+                if let Some(result) = self.advance(key) {
+                    return result;
+                }
+                // The key does not continue the sequence in progress, but it
+                // might start another one.
+                self.reset();
+                match self.advance(key) {
+                    Some(result) => {
+                        self.last = Some(time);
+                        result
+                    }
+                    None => SequenceMatch::NoMatch,
+                }
+            }
+            EventType::KeyRelease(key) => {
+                let key = normalize(*key);
+                self.pressed.remove(&key);
+                self.carryover.remove(&key);
+                self.state()
+            }
+            _ => self.state(),
+        }
+    }
+
+    fn node(&self) -> &Node {
+        self.path
+            .iter()
+            .fold(&self.root, |node, index| &node.children[*index].1)
+    }
+
+    /// Whether the pressed keys, apart from those held from the previous
+    /// chord, can be part of `chord`.
+    fn fits(&self, chord: &Chord) -> bool {
+        self.pressed
+            .iter()
+            .all(|key| chord.keys.contains(key) || self.carryover.contains(key))
+    }
+
+    fn complete(&self, chord: &Chord) -> bool {
+        chord.keys.iter().all(|key| self.pressed.contains(key))
+    }
+
+    /// Whether some keys of a chord are pressed, waiting for the rest.
+    fn building(&self) -> bool {
+        let fresh = self.pressed.difference(&self.carryover).next().is_some();
+        fresh
+            && self
+                .node()
+                .children
+                .iter()
+                .any(|(chord, _)| self.fits(chord) && !self.complete(chord))
+    }
+
+    fn state(&self) -> SequenceMatch {
+        if !self.path.is_empty() || self.building() {
+            SequenceMatch::Partial
+        } else {
+            SequenceMatch::NoMatch
+        }
+    }
+
+    /// Moves down the trie if pressing `key` typed one of the next chords.
+    /// Returns None if `key` does not belong to any of them.
+    fn advance(&mut self, key: Key) -> Option<SequenceMatch> {
+        let mut building = false;
+        let mut typed = None;
+        for (index, (chord, _)) in self.node().children.iter().enumerate() {
+            if !chord.keys.contains(&key) || !self.fits(chord) {
+                continue;
+            }
+            if !self.complete(chord) {
+                building = true;
+            } else if typed.is_none() || chord.keys.len() == self.pressed.len() {
+                // Prefer the chord matching every pressed key, "ctrl+s" over
+                // "s" while ctrl is still held from the previous chord.
+                typed = Some(index);
+            }
+        }
+        match typed {
+            Some(index) => {
+                self.path.push(index);
+                self.carryover = self.pressed.clone();
+                match self.node().id {
+                    Some(id) => {
+                        self.reset();
+                        Some(SequenceMatch::Complete(id))
+                    }
+                    None => Some(SequenceMatch::Partial),
+                }
+            }
+            None if building => Some(SequenceMatch::Partial),
+            None => None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    struct Typist {
+        matcher: SequenceMatcher,
+        time: Instant,
+    }
+
+    impl Typist {
+        fn press(&mut self, key: Key) -> SequenceMatch {
+            self.matcher.handle_at(&EventType::KeyPress(key), self.time)
+        }
+
+        fn release(&mut self, key: Key) -> SequenceMatch {
+            self.matcher
+                .handle_at(&EventType::KeyRelease(key), self.time)
+        }
+
+        fn tap(&mut self, key: Key) -> SequenceMatch {
+            let result = self.press(key);
+            self.release(key);
+            result
+        }
+
+        fn wait(&mut self, millis: u64) {
+            self.time += Duration::from_millis(millis);
+        }
+    }
+
+    fn typist(sequences: &[&str]) -> (Typist, Vec<SequenceId>) {
+        let mut matcher = SequenceMatcher::new(Duration::from_millis(500));
+        let ids = sequences
+            .iter()
+            .map(|sequence| matcher.register(sequence.parse().unwrap()).unwrap())
+            .collect();
+        let typist = Typist {
+            matcher,
+            time: Instant::now(),
+        };
+        (typist, ids)
+    }
+
+    #[test]
+    fn test_sequence() {
+        let (mut typist, ids) = typist(&["ctrl+x ctrl+s", "ctrl+x s", "space f f"]);
+        assert_eq!(typist.press(Key::ControlRight), SequenceMatch::Partial);
+        assert_eq!(typist.tap(Key::KeyX), SequenceMatch::Partial);
+        assert_eq!(typist.matcher.pending(), vec![&"ctrl+x".parse().unwrap()]);
+        assert_eq!(typist.tap(Key::KeyS), SequenceMatch::Complete(ids[0]));
+        typist.release(Key::ControlRight);
+
+        typist.press(Key::ControlLeft);
+        typist.tap(Key::KeyX);
+        typist.release(Key::ControlLeft);
+        assert_eq!(typist.tap(Key::KeyS), SequenceMatch::Complete(ids[1]));
+
+        assert_eq!(typist.tap(Key::Space), SequenceMatch::Partial);
+        assert_eq!(typist.tap(Key::KeyF), SequenceMatch::Partial);
+        // A wrong key drops the sequence.
+        assert_eq!(typist.tap(Key::KeyG), SequenceMatch::NoMatch);
+        assert_eq!(typist.tap(Key::KeyF), SequenceMatch::NoMatch);
+        // Space is still held while typing f, the way fast typists do.
+        typist.press(Key::Space);
+        assert_eq!(typist.tap(Key::KeyF), SequenceMatch::Partial);
+        typist.release(Key::Space);
+        assert_eq!(typist.tap(Key::KeyF), SequenceMatch::Complete(ids[2]));
+    }
+
+    #[test]
+    fn test_chord() {
+        let (mut typist, ids) = typist(&["j+k", "j l"]);
+        // Any order, as long as both keys are down together.
+        assert_eq!(typist.press(Key::KeyK), SequenceMatch::Partial);
+        assert_eq!(typist.press(Key::KeyJ), SequenceMatch::Complete(ids[0]));
+        typist.release(Key::KeyJ);
+        typist.release(Key::KeyK);
+        // One after the other is a sequence, not the chord.
+        assert_eq!(typist.tap(Key::KeyJ), SequenceMatch::Partial);
+        assert_eq!(typist.tap(Key::KeyK), SequenceMatch::Partial);
+        typist.release(Key::KeyK);
+        assert_eq!(typist.tap(Key::KeyJ), SequenceMatch::Partial);
+        assert_eq!(typist.tap(Key::KeyL), SequenceMatch::Complete(ids[1]));
+    }
+
+    #[test]
+    fn test_timeout() {
+        let (mut typist, ids) = typist(&["space f f"]);
+        typist.tap(Key::Space);
+        typist.wait(400);
+        assert_eq!(typist.tap(Key::KeyF), SequenceMatch::Partial);
+        typist.wait(600);
+        assert!(typist.matcher.expire(typist.time));
+        assert!(typist.matcher.pending().is_empty());
+        assert_eq!(typist.tap(Key::KeyF), SequenceMatch::NoMatch);
+
+        typist.tap(Key::Space);
+        typist.tap(Key::KeyF);
+        typist.wait(600);
+        // Expired sequences get dropped even without calling expire.
+        assert_eq!(typist.tap(Key::KeyF), SequenceMatch::NoMatch);
+        assert_eq!(ids.len(), 1);
+    }
+
+    #[test]
+    fn test_register() {
+        let mut matcher = SequenceMatcher::new(Duration::from_secs(1));
+        let save = matcher.register("ctrl+x ctrl+s".parse().unwrap()).unwrap();
+        assert_eq!(matcher.register("ctrl+x ctrl+s".parse().unwrap()), None);
+        assert_eq!(matcher.register("ctrl+x".parse().unwrap()), None);
+        assert_eq!(matcher.register("ctrl+x ctrl+s a".parse().unwrap()), None);
+        assert!(matcher.unregister(save));
+        assert!(!matcher.unregister(save));
+        assert!(matcher.register("ctrl+x".parse().unwrap()).is_some());
+    }
+}
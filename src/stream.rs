@@ -0,0 +1,240 @@
+use crate::rdev::{Event, ListenError};
+use crate::{listen_with_handle, ListenHandle};
+use futures_core::Stream;
+use std::collections::VecDeque;
+use std::num::NonZeroUsize;
+use std::pin::Pin;
+use std::sync::{Arc, Mutex, MutexGuard, PoisonError};
+use std::task::{Context, Poll, Waker};
+use std::thread;
+
+/// What to do with incoming events when the buffer of a bounded
+/// `EventStream` is full.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub enum OverflowPolicy {
+    /// Drop the incoming event, keeping the ones already buffered.
+    DropNewest,
+    /// Drop the oldest buffered event to make room for the incoming one.
+    DropOldest,
+}
+
+struct Buffer {
+    events: VecDeque<Event>,
+    capacity: Option<usize>,
+    policy: OverflowPolicy,
+    dropped: u64,
+    closed: bool,
+    waker: Option<Waker>,
+}
+
+impl Buffer {
+    fn push(&mut self, event: Event) {
+        if let Some(capacity) = self.capacity {
+            if self.events.len() >= capacity {
+                self.dropped += 1;
+                match self.policy {
+                    OverflowPolicy::DropNewest => return,
+                    OverflowPolicy::DropOldest => {
+                        self.events.pop_front();
+                    }
+                }
+            }
+        }
+        self.events.push_back(event);
+    }
+}
+
+type SharedBuffer = Arc<Mutex<Buffer>>;
+
+fn lock(buffer: &SharedBuffer) -> MutexGuard<'_, Buffer> {
+    buffer.lock().unwrap_or_else(PoisonError::into_inner)
+}
+
+/// Lives inside the listen callback, the stream ends once it gets dropped
+/// along with the listener.
+struct Sender {
+    buffer: SharedBuffer,
+}
+
+impl Sender {
+    fn send(&self, event: Event) {
+        let mut buffer = lock(&self.buffer);
+        buffer.push(event);
+        if let Some(waker) = buffer.waker.take() {
+            waker.wake();
+        }
+    }
+}
+
+impl Drop for Sender {
+    fn drop(&mut self) {
+        let mut buffer = lock(&self.buffer);
+        buffer.closed = true;
+        if let Some(waker) = buffer.waker.take() {
+            waker.wake();
+        }
+    }
+}
+
+/// Stream of global events returned by `listen_stream`. The listener runs on
+/// a background thread and gets stopped when the stream is dropped, without
+/// waiting for it so that the executor doesn't block. The stream ends if the
+/// listener fails, `stop` then tells why.
+pub struct EventStream {
+    buffer: SharedBuffer,
+    handle: Option<ListenHandle>,
+}
+
+impl EventStream {
+    fn new(capacity: Option<usize>, policy: OverflowPolicy) -> Result<EventStream, ListenError> {
+        let buffer = Arc::new(Mutex::new(Buffer {
+            events: VecDeque::new(),
+            capacity,
+            policy,
+            dropped: 0,
+            closed: false,
+            waker: None,
+        }));
+        let sender = Sender {
+            buffer: buffer.clone(),
+        };
+        let handle = listen_with_handle(move |event| sender.send(event))?;
+        Ok(EventStream {
+            buffer,
+            handle: Some(handle),
+        })
+    }
+
+    /// Number of events dropped so far because the buffer was full.
+    pub fn dropped(&self) -> u64 {
+        lock(&self.buffer).dropped
+    }
+
+    /// Stops the listener and waits for it, returning the error it failed
+    /// with if the stream ended on its own. This blocks, unlike dropping the
+    /// stream.
+    pub fn stop(mut self) -> Result<(), ListenError> {
+        match self.handle.take() {
+            Some(handle) => handle.stop(),
+            None => Ok(()),
+        }
+    }
+}
+
+impl Stream for EventStream {
+    type Item = Event;
+
+    fn poll_next(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Event>> {
+        let mut buffer = lock(&self.buffer);
+        if let Some(event) = buffer.events.pop_front() {
+            return Poll::Ready(Some(event));
+        }
+        if buffer.closed {
+            return Poll::Ready(None);
+        }
+        buffer.waker = Some(cx.waker().clone());
+        Poll::Pending
+    }
+}
+
+impl Drop for EventStream {
+    fn drop(&mut self) {
+        // Waiting on the listener happens on a thread of its own.
+        if let Some(handle) = self.handle.take() {
+            thread::spawn(move || handle.stop().ok());
+        }
+    }
+}
+
+/// Listening to global events as an asynchronous `Stream`. Events are buffered
+/// without limit until polled.
+///
+/// ```no_run
+/// use futures::StreamExt;
+/// use rdev::listen_stream;
+///
+/// # async fn run() {
+/// let mut events = listen_stream().expect("Could not listen");
+/// while let Some(event) = events.next().await {
+///     println!("Received {:?}", event);
+/// }
+/// # }
+/// ```
+pub fn listen_stream() -> Result<EventStream, ListenError> {
+    EventStream::new(None, OverflowPolicy::DropNewest)
+}
+
+/// Same as `listen_stream`, but buffers at most `capacity` events. Once the
+/// buffer is full, `policy` decides which events get dropped.
+///
+/// ```no_run
+/// use rdev::{listen_stream_bounded, OverflowPolicy};
+/// use std::num::NonZeroUsize;
+///
+/// let capacity = NonZeroUsize::new(64).unwrap();
+/// let events = listen_stream_bounded(capacity, OverflowPolicy::DropOldest);
+/// ```
+pub fn listen_stream_bounded(
+    capacity: NonZeroUsize,
+    policy: OverflowPolicy,
+) -> Result<EventStream, ListenError> {
+    EventStream::new(Some(capacity.get()), policy)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::rdev::{EventType, Key, Modifiers};
+    use std::time::SystemTime;
+
+    fn buffer(capacity: usize, policy: OverflowPolicy) -> Buffer {
+        Buffer {
+            events: VecDeque::new(),
+            capacity: Some(capacity),
+            policy,
+            dropped: 0,
+            closed: false,
+            waker: None,
+        }
+    }
+
+    fn event(key: Key) -> Event {
+        Event {
+            time: SystemTime::now(),
+            name: None,
+            event_type: EventType::KeyPress(key),
+            modifiers: Modifiers::empty(),
+            device: None,
+        }
+    }
+
+    fn keys(buffer: &Buffer) -> Vec<EventType> {
+        buffer.events.iter().map(|event| event.event_type).collect()
+    }
+
+    #[test]
+    fn test_overflow_policy() {
+        let mut newest = buffer(2, OverflowPolicy::DropNewest);
+        let mut oldest = buffer(2, OverflowPolicy::DropOldest);
+        for key in &[Key::KeyA, Key::KeyB, Key::KeyC] {
+            newest.push(event(*key));
+            oldest.push(event(*key));
+        }
+        assert_eq!(
+            keys(&newest),
+            vec![
+                EventType::KeyPress(Key::KeyA),
+                EventType::KeyPress(Key::KeyB)
+            ]
+        );
+        assert_eq!(
+            keys(&oldest),
+            vec![
+                EventType::KeyPress(Key::KeyB),
+                EventType::KeyPress(Key::KeyC)
+            ]
+        );
+        assert_eq!(newest.dropped, 1);
+        assert_eq!(oldest.dropped, 1);
+    }
+}
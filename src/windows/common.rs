@@ -1,4 +1,4 @@
-use crate::rdev::{Button, EventType};
+use crate::rdev::{Button, EventType, Modifiers};
 use crate::windows::keyboard::Keyboard;
 use crate::windows::keycodes::key_from_code;
 use lazy_static::lazy_static;
@@ -11,10 +11,11 @@ use winapi::shared::ntdef::LONG;
 use winapi::shared::windef::HHOOK;
 use winapi::um::errhandlingapi::GetLastError;
 use winapi::um::winuser::{
-    SetWindowsHookExA, KBDLLHOOKSTRUCT, MSLLHOOKSTRUCT, WHEEL_DELTA, WH_KEYBOARD_LL, WH_MOUSE_LL,
-    WM_KEYDOWN, WM_KEYUP, WM_LBUTTONDOWN, WM_LBUTTONUP, WM_MBUTTONDOWN, WM_MBUTTONUP,
-    WM_MOUSEHWHEEL, WM_MOUSEMOVE, WM_MOUSEWHEEL, WM_RBUTTONDOWN, WM_RBUTTONUP, WM_SYSKEYDOWN,
-    WM_SYSKEYUP, WM_XBUTTONDOWN, WM_XBUTTONUP,
+    GetAsyncKeyState, GetKeyState, SetWindowsHookExA, KBDLLHOOKSTRUCT, MSLLHOOKSTRUCT, VK_CAPITAL,
+    VK_CONTROL, VK_LMENU, VK_LWIN, VK_NUMLOCK, VK_RMENU, VK_RWIN, VK_SHIFT, WHEEL_DELTA,
+    WH_KEYBOARD_LL, WH_MOUSE_LL, WM_KEYDOWN, WM_KEYUP, WM_LBUTTONDOWN, WM_LBUTTONUP,
+    WM_MBUTTONDOWN, WM_MBUTTONUP, WM_MOUSEHWHEEL, WM_MOUSEMOVE, WM_MOUSEWHEEL, WM_RBUTTONDOWN,
+    WM_RBUTTONUP, WM_SYSKEYDOWN, WM_SYSKEYUP, WM_XBUTTONDOWN, WM_XBUTTONUP,
 };
 pub const TRUE: i32 = 1;
 pub const FALSE: i32 = 0;
@@ -99,13 +100,40 @@ pub unsafe fn convert(param: WPARAM, lpdata: LPARAM) -> Option<EventType> {
     }
 }
 
+/// Low level hooks run before the key state gets updated, `update` applies
+/// the event's own key.
+pub unsafe fn get_modifiers(event_type: &EventType) -> Modifiers {
+    let held = [
+        (VK_SHIFT, Modifiers::SHIFT),
+        (VK_CONTROL, Modifiers::CTRL),
+        (VK_LMENU, Modifiers::ALT),
+        (VK_RMENU, Modifiers::ALT_GR),
+        (VK_LWIN, Modifiers::META),
+        (VK_RWIN, Modifiers::META),
+    ];
+    let mut modifiers = Modifiers::empty();
+    for (code, modifier) in held.iter() {
+        if GetAsyncKeyState(*code) as u16 & 0x8000 != 0 {
+            modifiers |= *modifier;
+        }
+    }
+    if GetKeyState(VK_CAPITAL) & 1 != 0 {
+        modifiers |= Modifiers::CAPS_LOCK;
+    }
+    if GetKeyState(VK_NUMLOCK) & 1 != 0 {
+        modifiers |= Modifiers::NUM_LOCK;
+    }
+    modifiers.update(event_type);
+    modifiers
+}
+
 type RawCallback = unsafe extern "system" fn(code: c_int, param: WPARAM, lpdata: LPARAM) -> LRESULT;
 pub enum HookError {
     Mouse(DWORD),
     Key(DWORD),
 }
 
-pub unsafe fn set_key_hook(callback: RawCallback) -> Result<(), HookError> {
+pub unsafe fn set_key_hook(callback: RawCallback) -> Result<HHOOK, HookError> {
     let hook = SetWindowsHookExA(WH_KEYBOARD_LL, Some(callback), null_mut(), 0);
 
     if hook.is_null() {
@@ -113,15 +141,15 @@ pub unsafe fn set_key_hook(callback: RawCallback) -> Result<(), HookError> {
         return Err(HookError::Key(error));
     }
     HOOK = hook;
-    Ok(())
+    Ok(hook)
 }
 
-pub unsafe fn set_mouse_hook(callback: RawCallback) -> Result<(), HookError> {
+pub unsafe fn set_mouse_hook(callback: RawCallback) -> Result<HHOOK, HookError> {
     let hook = SetWindowsHookExA(WH_MOUSE_LL, Some(callback), null_mut(), 0);
     if hook.is_null() {
         let error = GetLastError();
         return Err(HookError::Mouse(error));
     }
     HOOK = hook;
-    Ok(())
+    Ok(hook)
 }
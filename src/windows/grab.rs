@@ -1,5 +1,7 @@
 use crate::rdev::{Event, EventType, GrabError};
-use crate::windows::common::{convert, set_key_hook, set_mouse_hook, HookError, HOOK, KEYBOARD};
+use crate::windows::common::{
+    convert, get_modifiers, set_key_hook, set_mouse_hook, HookError, HOOK, KEYBOARD,
+};
 use std::ptr::null_mut;
 use std::time::SystemTime;
 use winapi::um::winuser::{CallNextHookEx, GetMessageA, HC_ACTION};
@@ -18,9 +20,11 @@ unsafe extern "system" fn raw_callback(code: i32, param: usize, lpdata: isize) -
                 _ => None,
             };
             let event = Event {
+                modifiers: get_modifiers(&event_type),
                 event_type,
                 time: SystemTime::now(),
                 name,
+                device: None,
             };
             if let Some(callback) = &mut GLOBAL_CALLBACK {
                 if callback(event).is_none() {
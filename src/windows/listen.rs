@@ -1,10 +1,21 @@
 use crate::rdev::{Event, EventType, ListenError};
-use crate::windows::common::{convert, set_key_hook, set_mouse_hook, HookError, HOOK, KEYBOARD};
+use crate::windows::common::{
+    convert, get_modifiers, set_key_hook, set_mouse_hook, HookError, HOOK, KEYBOARD,
+};
+use std::io;
+use std::mem::MaybeUninit;
 use std::os::raw::c_int;
+use std::panic;
 use std::ptr::null_mut;
+use std::sync::mpsc::sync_channel;
+use std::thread::{self, JoinHandle};
 use std::time::SystemTime;
-use winapi::shared::minwindef::{LPARAM, LRESULT, WPARAM};
-use winapi::um::winuser::{CallNextHookEx, GetMessageA, HC_ACTION};
+use winapi::shared::minwindef::{DWORD, LPARAM, LRESULT, WPARAM};
+use winapi::um::processthreadsapi::GetCurrentThreadId;
+use winapi::um::winuser::{
+    CallNextHookEx, GetMessageA, PeekMessageA, PostThreadMessageA, UnhookWindowsHookEx, HC_ACTION,
+    MSG, PM_NOREMOVE, WM_QUIT, WM_USER,
+};
 
 static mut GLOBAL_CALLBACK: Option<Box<dyn FnMut(Event)>> = None;
 
@@ -29,9 +40,11 @@ unsafe extern "system" fn raw_callback(code: c_int, param: WPARAM, lpdata: LPARA
                 _ => None,
             };
             let event = Event {
+                modifiers: get_modifiers(&event_type),
                 event_type,
                 time: SystemTime::now(),
                 name,
+                device: None,
             };
             if let Some(callback) = &mut GLOBAL_CALLBACK {
                 callback(event);
@@ -54,3 +67,68 @@ where
     }
     Ok(())
 }
+
+pub fn listen_with_handle<T>(callback: T) -> Result<ListenHandle, ListenError>
+where
+    T: FnMut(Event) + Send + 'static,
+{
+    let (ready_send, ready_recv) = sync_channel(1);
+    let thread = thread::spawn(move || unsafe {
+        GLOBAL_CALLBACK = Some(Box::new(callback));
+        // Returning before sending the thread id tells why it isn't ready.
+        let key_hook = match set_key_hook(raw_callback) {
+            Ok(hook) => hook,
+            Err(error) => {
+                GLOBAL_CALLBACK = None;
+                return Err(error.into());
+            }
+        };
+        let mouse_hook = match set_mouse_hook(raw_callback) {
+            Ok(hook) => hook,
+            Err(error) => {
+                UnhookWindowsHookEx(key_hook);
+                GLOBAL_CALLBACK = None;
+                return Err(error.into());
+            }
+        };
+        // Makes sure the thread has a message queue before anyone posts to it.
+        let mut msg = MaybeUninit::<MSG>::uninit();
+        PeekMessageA(msg.as_mut_ptr(), null_mut(), WM_USER, WM_USER, PM_NOREMOVE);
+        ready_send.send(GetCurrentThreadId()).ok();
+
+        // Pumps messages until the handle posts WM_QUIT.
+        while GetMessageA(msg.as_mut_ptr(), null_mut(), 0, 0) > 0 {}
+        UnhookWindowsHookEx(mouse_hook);
+        UnhookWindowsHookEx(key_hook);
+        GLOBAL_CALLBACK = None;
+        Ok(())
+    });
+    match ready_recv.recv() {
+        Ok(thread_id) => Ok(ListenHandle { thread_id, thread }),
+        // The thread failed to set the hooks, or panicked.
+        Err(_) => match thread.join() {
+            Ok(Err(error)) => Err(error),
+            Ok(Ok(())) => unreachable!("The listener thread only returns Ok once ready"),
+            Err(error) => panic::resume_unwind(error),
+        },
+    }
+}
+
+/// Handle on a listener started with `listen_with_handle`.
+pub struct ListenHandle {
+    thread_id: DWORD,
+    thread: JoinHandle<Result<(), ListenError>>,
+}
+
+impl ListenHandle {
+    /// Stops the listener and waits for the hooks to be removed.
+    pub fn stop(self) -> Result<(), ListenError> {
+        if unsafe { PostThreadMessageA(self.thread_id, WM_QUIT, 0, 0) } == 0 {
+            return Err(io::Error::last_os_error().into());
+        }
+        match self.thread.join() {
+            Ok(result) => result,
+            Err(error) => panic::resume_unwind(error),
+        }
+    }
+}
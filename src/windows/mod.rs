@@ -13,5 +13,5 @@ pub use crate::windows::display::display_size;
 #[cfg(feature = "unstable_grab")]
 pub use crate::windows::grab::grab;
 pub use crate::windows::keyboard::Keyboard;
-pub use crate::windows::listen::listen;
-pub use crate::windows::simulate::simulate;
+pub use crate::windows::listen::{listen, listen_with_handle, ListenHandle};
+pub use crate::windows::simulate::{simulate, Simulator};
@@ -0,0 +1,102 @@
+#![cfg(target_os = "linux")]
+use evdev_rs::enums::{EventCode, EventType as EvdevEventType, EV_KEY, EV_SYN};
+use evdev_rs::{Device, InputEvent, TimeVal, UInputDevice};
+use rdev::{
+    listen_with_handle, set_listen_backend, set_simulate_backend, simulate, Backend, Event,
+    EventType, Key,
+};
+use serial_test::serial;
+use std::error::Error;
+use std::sync::mpsc::{channel, Receiver};
+use std::thread;
+use std::time::Duration;
+
+static NAME: &str = "rdev test keyboard";
+
+fn virtual_keyboard() -> Result<UInputDevice, Box<dyn Error>> {
+    let device = Device::new().ok_or("Could not create a device")?;
+    device.set_name(NAME);
+    device.enable(&EvdevEventType::EV_KEY)?;
+    device.enable(&EventCode::EV_KEY(EV_KEY::KEY_A))?;
+    device.enable(&EvdevEventType::EV_SYN)?;
+    device.enable(&EventCode::EV_SYN(EV_SYN::SYN_REPORT))?;
+    Ok(UInputDevice::create_from_device(&device)?)
+}
+
+fn write_key(keyboard: &UInputDevice, value: i32) -> Result<(), Box<dyn Error>> {
+    let time = TimeVal::new(0, 0);
+    keyboard.write_event(&InputEvent::new(
+        &time,
+        &EventCode::EV_KEY(EV_KEY::KEY_A),
+        value,
+    ))?;
+    keyboard.write_event(&InputEvent::new(
+        &time,
+        &EventCode::EV_SYN(EV_SYN::SYN_REPORT),
+        0,
+    ))?;
+    Ok(())
+}
+
+/// Next event from the device called `name`, other devices are in use too.
+fn next_event(recv: &Receiver<Event>, name: &str) -> Result<EventType, Box<dyn Error>> {
+    loop {
+        let event = recv.recv_timeout(Duration::from_secs(1))?;
+        if event.device.map(|device| device.name).as_deref() == Some(name) {
+            return Ok(event.event_type);
+        }
+    }
+}
+
+#[test]
+#[serial]
+fn test_listen_evdev() -> Result<(), Box<dyn Error>> {
+    set_listen_backend(Some(Backend::Evdev));
+    let (send, recv) = channel();
+    let handle = listen_with_handle(move |event| {
+        send.send(event).ok();
+    })
+    .expect("Could not listen");
+    // Plugged in while listening, so that it gets picked up through inotify
+    // once udev is done with it.
+    let keyboard = virtual_keyboard()?;
+    thread::sleep(Duration::from_millis(500));
+
+    write_key(&keyboard, 1)?;
+    write_key(&keyboard, 0)?;
+    let press = next_event(&recv, NAME);
+    let release = next_event(&recv, NAME);
+    handle.stop().expect("Could not stop listening");
+    set_listen_backend(None);
+
+    assert_eq!(press?, EventType::KeyPress(Key::KeyA));
+    assert_eq!(release?, EventType::KeyRelease(Key::KeyA));
+    Ok(())
+}
+
+#[test]
+#[serial]
+fn test_simulate_uinput() -> Result<(), Box<dyn Error>> {
+    set_listen_backend(Some(Backend::Evdev));
+    set_simulate_backend(Some(Backend::Uinput));
+    let (send, recv) = channel();
+    let handle = listen_with_handle(move |event| {
+        send.send(event).ok();
+    })
+    .expect("Could not listen");
+
+    // The virtual devices get created by the first event.
+    simulate(&EventType::KeyRelease(Key::ShiftLeft))?;
+    thread::sleep(Duration::from_millis(500));
+    simulate(&EventType::KeyPress(Key::KeyA))?;
+    simulate(&EventType::KeyRelease(Key::KeyA))?;
+    let press = next_event(&recv, "rdev virtual input");
+    let release = next_event(&recv, "rdev virtual input");
+    handle.stop().expect("Could not stop listening");
+    set_listen_backend(None);
+    set_simulate_backend(None);
+
+    assert_eq!(press?, EventType::KeyPress(Key::KeyA));
+    assert_eq!(release?, EventType::KeyRelease(Key::KeyA));
+    Ok(())
+}
@@ -70,3 +70,16 @@ fn test_grab() -> Result<(), Box<dyn Error>> {
     };
     Ok(())
 }
+
+#[test]
+#[serial]
+#[cfg(target_os = "linux")]
+fn test_grab_no_device() {
+    use rdev::{DeviceFilter, GrabConfig, GrabError};
+
+    let config = GrabConfig::new().include(DeviceFilter::Name("rdev no such device".to_string()));
+    match config.grab(Some) {
+        Err(GrabError::NoDeviceError) => (),
+        result => panic!("Grabbed no device, yet got {:?}", result),
+    }
+}
@@ -1,5 +1,7 @@
 use lazy_static::lazy_static;
-use rdev::{listen, simulate, Button, Event, EventType, Key};
+use rdev::{
+    events, listen, listen_with_handle, simulate, Button, Event, EventType, Key, Simulator,
+};
 use serial_test::serial;
 use std::error::Error;
 use std::iter::Iterator;
@@ -72,3 +74,131 @@ fn test_listen_and_simulate() -> Result<(), Box<dyn Error>> {
     let mut events = events.chain(click_events);
     sim_then_listen(&mut events)
 }
+
+#[test]
+#[serial]
+fn test_listen_with_handle() -> Result<(), Box<dyn Error>> {
+    let timeout = Duration::from_secs(1);
+    // Listening has to work again once stopped.
+    for _ in 0..2 {
+        let (send, recv) = channel();
+        let handle = listen_with_handle(move |event| {
+            send.send(event).ok();
+        })
+        .expect("Could not listen");
+        thread::sleep(Duration::from_millis(100));
+
+        let event_type = EventType::KeyPress(Key::KeyS);
+        simulate(&event_type)?;
+        assert_eq!(recv.recv_timeout(timeout)?.event_type, event_type);
+        simulate(&EventType::KeyRelease(Key::KeyS))?;
+
+        handle.stop().expect("Could not stop listening");
+        // The callback got dropped along with the listener.
+        while recv.recv_timeout(timeout).is_ok() {}
+    }
+    Ok(())
+}
+
+#[cfg(target_os = "linux")]
+#[test]
+#[serial]
+fn test_concurrent_listeners() -> Result<(), Box<dyn Error>> {
+    let timeout = Duration::from_secs(1);
+    let (send, recv) = channel();
+    let handles = (0..2)
+        .map(|listener| {
+            let send = send.clone();
+            listen_with_handle(move |event| {
+                send.send((listener, event)).ok();
+            })
+            .expect("Could not listen")
+        })
+        .collect::<Vec<_>>();
+    thread::sleep(Duration::from_millis(100));
+
+    let event_type = EventType::KeyPress(Key::KeyS);
+    simulate(&event_type)?;
+    simulate(&EventType::KeyRelease(Key::KeyS))?;
+    let mut listeners = Vec::new();
+    // Both listeners get the press and the release, in any order.
+    for _ in 0..4 {
+        let (listener, event) = recv.recv_timeout(timeout)?;
+        if event.event_type == event_type {
+            listeners.push(listener);
+        }
+    }
+    listeners.sort_unstable();
+    assert_eq!(listeners, vec![0, 1]);
+
+    for handle in handles {
+        handle.stop().expect("Could not stop listening");
+    }
+    Ok(())
+}
+
+#[test]
+#[serial]
+fn test_events() -> Result<(), Box<dyn Error>> {
+    let timeout = Duration::from_secs(1);
+    let events = events().expect("Could not listen");
+    thread::sleep(Duration::from_millis(100));
+
+    let event_type = EventType::KeyPress(Key::KeyS);
+    simulate(&event_type)?;
+    simulate(&EventType::KeyRelease(Key::KeyS))?;
+    assert_eq!(events.recv_timeout(timeout)?.event_type, event_type);
+    let mut events = events.skip(1);
+    simulate(&event_type)?;
+    assert_eq!(
+        events.next().map(|event| event.event_type),
+        Some(event_type)
+    );
+    simulate(&EventType::KeyRelease(Key::KeyS))?;
+    Ok(())
+}
+
+#[test]
+#[serial]
+fn test_simulator() -> Result<(), Box<dyn Error>> {
+    let timeout = Duration::from_secs(1);
+    let events = events().expect("Could not listen");
+    thread::sleep(Duration::from_millis(100));
+
+    let path = (0..480)
+        .map(|pixel| EventType::MouseMove {
+            x: pixel as f64,
+            y: pixel as f64,
+        })
+        .collect::<Vec<_>>();
+    let mut simulator = Simulator::new()?;
+    simulator.send_batch(&path)?;
+    simulator.flush()?;
+    for event_type in path {
+        assert_eq!(events.recv_timeout(timeout)?.event_type, event_type);
+    }
+    Ok(())
+}
+
+#[cfg(target_os = "linux")]
+#[test]
+#[serial]
+#[ignore]
+/// Needs an X server, and a layout without a key for ✓ so that it gets
+/// typed on a spare keycode.
+fn test_simulate_text() -> Result<(), Box<dyn Error>> {
+    let timeout = Duration::from_secs(1);
+    let events = events().expect("Could not listen");
+    thread::sleep(Duration::from_millis(100));
+
+    let text = "aB ✓c✓";
+    rdev::simulate_text(text)?;
+    let mut typed = String::new();
+    while let Ok(event) = events.recv_timeout(timeout) {
+        if let (EventType::KeyPress(_), Some(name)) = (event.event_type, event.name) {
+            typed.push_str(&name);
+        }
+    }
+    assert_eq!(typed, text);
+    Ok(())
+}